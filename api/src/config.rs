@@ -0,0 +1,232 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use config::{Config, ConfigError, Environment, File};
+use serde::Deserialize;
+use tracing_subscriber::filter::LevelFilter;
+
+/// Config file read when `--config` isn't given. It's optional: everything
+/// has a default or can come from the environment.
+const DEFAULT_CONFIG_FILE: &str = "api.toml";
+
+/// Prefix of environment variables overriding the config file, with `__`
+/// between nested keys, e.g. `MESSAGING_AMQP__URL`.
+const ENV_PREFIX: &str = "MESSAGING";
+
+/// Longest idempotency window accepted: a year is far more than any client
+/// retries for, and keeps the window well inside what `chrono` can represent.
+pub const MAX_IDEMPOTENCY_WINDOW_HOURS: i64 = 24 * 365;
+
+/// Command-line flags that override the config file and environment.
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    /// Config file to read [default: api.toml, if present]
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Address to listen on
+    #[arg(long, global = true)]
+    pub bind: Option<String>,
+
+    #[arg(long, global = true)]
+    pub amqp_url: Option<String>,
+
+    /// SQLite database holding messages, usage and API keys
+    #[arg(long, global = true)]
+    pub database_path: Option<String>,
+
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiConfig {
+    pub server: ServerConfig,
+    pub amqp: AmqpConfig,
+    pub store: StoreConfig,
+    pub logging: LoggingConfig,
+    pub limits: LimitsConfig,
+    pub webhooks: WebhookConfig,
+    pub keywords: KeywordsConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Serves HTTPS when set.
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain.
+    pub cert_path: PathBuf,
+    /// PEM private key.
+    pub key_path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AmqpConfig {
+    pub url: String,
+    /// Queue accepted messages are published to for the workers.
+    pub outbound_queue: String,
+    /// Queue received SMS are published to.
+    pub inbound_queue: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StoreConfig {
+    /// SQLite database holding messages, usage and the API key store.
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoggingConfig {
+    /// One of off, error, warn, info, debug or trace.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Full,
+    Compact,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LimitsConfig {
+    /// Requests per second each API key's bucket refills at.
+    pub rate_per_second: f64,
+    /// Requests an API key may make in a burst.
+    pub burst: f64,
+    /// Messages per API key per UTC day; unlimited when unset.
+    pub daily_quota: Option<u32>,
+    /// Messages per API key per UTC month; unlimited when unset.
+    pub monthly_quota: Option<u32>,
+    pub idempotency_window_hours: i64,
+    /// Largest number of messages accepted by a single `/send_sms/batch` call.
+    pub max_batch_size: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookConfig {
+    /// Shared secret ClickSend callbacks must present; webhooks are refused
+    /// when unset.
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeywordsConfig {
+    pub opt_out: Vec<String>,
+    pub opt_in: Vec<String>,
+}
+
+impl ApiConfig {
+    /// Layers, lowest precedence first: built-in defaults, the config file,
+    /// `MESSAGING_*` environment variables, then command-line flags.
+    pub fn load(args: &ConfigArgs) -> Result<Self, String> {
+        let config =
+            layered(args).map_err(|err| format!("Failed to load configuration: {}", err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks what deserializing can't, reporting every problem at once.
+    fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+
+        if let Some(tls) = &self.server.tls {
+            for path in [&tls.cert_path, &tls.key_path] {
+                if !path.is_file() {
+                    problems.push(format!("server.tls: {} does not exist", path.display()));
+                }
+            }
+        }
+        if self.amqp.outbound_queue.is_empty() || self.amqp.inbound_queue.is_empty() {
+            problems.push("amqp: queue names must not be empty".to_string());
+        } else if self.amqp.outbound_queue == self.amqp.inbound_queue {
+            problems.push("amqp: outbound_queue and inbound_queue must differ".to_string());
+        }
+        if self.store.path.is_empty() {
+            problems.push("store.path must not be empty".to_string());
+        }
+        if let Err(err) = self.log_level() {
+            problems.push(err);
+        }
+
+        let limits = &self.limits;
+        if limits.rate_per_second <= 0.0 {
+            problems.push("limits.rate_per_second must be positive".to_string());
+        }
+        if limits.burst < 1.0 {
+            problems.push("limits.burst must be at least 1".to_string());
+        }
+        if !(1..=MAX_IDEMPOTENCY_WINDOW_HOURS).contains(&limits.idempotency_window_hours) {
+            problems.push(format!(
+                "limits.idempotency_window_hours must be between 1 and {}",
+                MAX_IDEMPOTENCY_WINDOW_HOURS
+            ));
+        }
+        if limits.max_batch_size == 0 {
+            problems.push("limits.max_batch_size must be positive".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Invalid configuration:\n  {}",
+                problems.join("\n  ")
+            ))
+        }
+    }
+
+    pub fn log_level(&self) -> Result<LevelFilter, String> {
+        self.logging
+            .level
+            .parse()
+            .map_err(|_| format!("logging.level: unknown level '{}'", self.logging.level))
+    }
+}
+
+fn layered(args: &ConfigArgs) -> Result<ApiConfig, ConfigError> {
+    let file = match &args.config {
+        Some(path) => File::from(path.as_path()).required(true),
+        None => File::from(Path::new(DEFAULT_CONFIG_FILE)).required(false),
+    };
+    let environment = Environment::with_prefix(ENV_PREFIX)
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+        .list_separator(",")
+        .with_list_parse_key("keywords.opt_out")
+        .with_list_parse_key("keywords.opt_in");
+
+    Config::builder()
+        .set_default("server.bind", "0.0.0.0:3000")?
+        .set_default("amqp.url", "amqp://127.0.0.1:5672/%2f")?
+        .set_default("amqp.outbound_queue", "sms_queue")?
+        .set_default("amqp.inbound_queue", "sms_inbound")?
+        .set_default("store.path", "messaging.db")?
+        .set_default("logging.level", "info")?
+        .set_default("logging.format", "full")?
+        .set_default("limits.rate_per_second", 10.0)?
+        .set_default("limits.burst", 20.0)?
+        .set_default("limits.idempotency_window_hours", 24)?
+        .set_default("limits.max_batch_size", 10_000)?
+        .set_default("webhooks.secret", None::<String>)?
+        .set_default("keywords.opt_out", vec!["STOP", "UNSUBSCRIBE"])?
+        .set_default("keywords.opt_in", vec!["START"])?
+        .add_source(file)
+        .add_source(environment)
+        .set_override_option("server.bind", args.bind.clone())?
+        .set_override_option("amqp.url", args.amqp_url.clone())?
+        .set_override_option("store.path", args.database_path.clone())?
+        .set_override_option("logging.level", args.log_level.clone())?
+        .build()?
+        .try_deserialize()
+}
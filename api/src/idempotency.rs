@@ -0,0 +1,186 @@
+use std::future::Future;
+
+use axum::{
+    body::{self, Body},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use shared::{ApiResponse, ErrorCode};
+use store::{ApiKeyRecord, IdempotencyClaim};
+use tracing::{error, warn};
+
+use crate::{routes::api_error, AppState};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Set on responses replayed from an earlier request.
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Fingerprint of a request body, used to spot a key reused for a different
+/// request.
+pub fn request_hash<T: Serialize>(request: &T) -> String {
+    let body = serde_json::to_vec(request).unwrap_or_default();
+    hex::encode(Sha256::digest(body))
+}
+
+/// Runs `handler` unless the request's `Idempotency-Key` was already used by
+/// the same API key, in which case the first response is returned again.
+///
+/// Server errors and quota refusals aren't remembered, since nothing was
+/// sent: a retry with the same key is handled afresh.
+pub async fn idempotent<F, Fut>(
+    app_state: &AppState,
+    key: &ApiKeyRecord,
+    headers: &HeaderMap,
+    request_hash: &str,
+    handler: F,
+) -> Response
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Response>,
+{
+    let Some(value) = headers.get(IDEMPOTENCY_KEY) else {
+        return handler().await;
+    };
+    let idempotency_key = match value.to_str() {
+        Ok(value) if !value.is_empty() && value.len() <= MAX_KEY_LENGTH => value,
+        _ => {
+            return api_error(
+                StatusCode::BAD_REQUEST,
+                &format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    MAX_KEY_LENGTH
+                ),
+            )
+        }
+    };
+
+    let store = &app_state.store;
+    let claim = store
+        .claim_idempotency_key(
+            &key.id,
+            idempotency_key,
+            request_hash,
+            app_state.idempotency_window,
+            Utc::now(),
+        )
+        .await;
+
+    match claim {
+        Ok(IdempotencyClaim::New) => {}
+        Ok(IdempotencyClaim::Replay {
+            status,
+            headers,
+            body,
+        }) => return replay(status, headers, body),
+        Ok(IdempotencyClaim::InProgress) => {
+            return conflict(
+                "A request with this Idempotency-Key is still being processed",
+                ErrorCode::RequestInProgress,
+            )
+        }
+        Ok(IdempotencyClaim::Mismatch) => {
+            return conflict(
+                "Idempotency-Key was already used for a different request",
+                ErrorCode::IdempotencyKeyReused,
+            )
+        }
+        Err(err) => {
+            error!("Failed to claim idempotency key: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check Idempotency-Key",
+            );
+        }
+    }
+
+    let response = handler().await;
+    let status = response.status();
+
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        if let Err(err) = store
+            .release_idempotency_key(&key.id, idempotency_key)
+            .await
+        {
+            error!("Failed to release idempotency key: {}", err);
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let bytes = match body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            error!("Failed to read response to remember: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read the response",
+            );
+        }
+    };
+    // The body is replayed as is, so its length is worked out again
+    let remembered_headers = parts
+        .headers
+        .iter()
+        .filter(|(name, _)| *name != header::CONTENT_LENGTH)
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    if let Err(err) = store
+        .complete_idempotency_key(
+            &key.id,
+            idempotency_key,
+            status.as_u16(),
+            remembered_headers,
+            bytes.to_vec(),
+        )
+        .await
+    {
+        error!("Failed to save idempotent response: {}", err);
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Rebuilds a remembered response, with the headers it was first sent with
+/// (remaining quota, for one).
+fn replay(status: u16, headers: Vec<(String, String)>, body: Vec<u8>) -> Response {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::OK);
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    let response_headers = response.headers_mut();
+    for (name, value) in headers {
+        match (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            (Ok(name), Ok(value)) => {
+                response_headers.append(name, value);
+            }
+            _ => warn!("Dropped a malformed remembered header"),
+        }
+    }
+    // Responses remembered before their headers were have only JSON bodies
+    if !response_headers.contains_key(header::CONTENT_TYPE) {
+        response_headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+    }
+    response_headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+fn conflict(message: &str, code: ErrorCode) -> Response {
+    (
+        StatusCode::CONFLICT,
+        Json(ApiResponse {
+            status: 409,
+            message: message.to_string(),
+            code: Some(code),
+        }),
+    )
+        .into_response()
+}
@@ -0,0 +1,92 @@
+use chrono::{Duration, Utc};
+use clap::Subcommand;
+use store::{NewApiKey, Scope, Store};
+
+#[derive(Subcommand, Debug)]
+pub enum KeysCommand {
+    /// Create a key and print its token, which is shown only this once
+    Create {
+        #[arg(short, long)]
+        name: String,
+
+        #[arg(short, long)]
+        owner: String,
+
+        /// Comma-separated: send, read-status, admin
+        #[arg(short, long, value_delimiter = ',', default_value = "send,read-status")]
+        scopes: Vec<Scope>,
+
+        /// Days until the key stops working; never expires when omitted
+        #[arg(short, long, value_parser = clap::value_parser!(i64).range(1..))]
+        expires_in_days: Option<i64>,
+    },
+    /// List all keys, including revoked and expired ones
+    List,
+    /// Revoke a key by ID
+    Revoke { id: String },
+}
+
+pub async fn run(store: &Store, command: KeysCommand) -> Result<(), String> {
+    match command {
+        KeysCommand::Create {
+            name,
+            owner,
+            scopes,
+            expires_in_days,
+        } => {
+            let expires_at = expires_in_days
+                .map(|days| {
+                    Duration::try_days(days)
+                        .and_then(|expires_in| Utc::now().checked_add_signed(expires_in))
+                        .ok_or_else(|| format!("--expires-in-days {} is too far away", days))
+                })
+                .transpose()?;
+            let new_key = NewApiKey {
+                name,
+                owner,
+                scopes,
+                expires_at,
+            };
+            let (key, token) = store
+                .create_api_key(new_key)
+                .await
+                .map_err(|err| err.to_string())?;
+            println!("Created key {} ({})", key.id, key.name);
+            println!("{}", token);
+        }
+        KeysCommand::List => {
+            let now = Utc::now();
+            for key in store.list_api_keys().await.map_err(|err| err.to_string())? {
+                let state = if key.revoked_at.is_some() {
+                    "revoked"
+                } else if !key.is_active(now) {
+                    "expired"
+                } else {
+                    "active"
+                };
+                let scopes: Vec<_> = key.scopes.iter().map(Scope::as_str).collect();
+                println!(
+                    "{}  {:<8} {:<20} {:<24} {:<28} created {}  expires {}",
+                    key.id,
+                    state,
+                    key.name,
+                    key.owner,
+                    scopes.join(","),
+                    key.created_at.format("%Y-%m-%d"),
+                    key.expires_at
+                        .map(|at| at.format("%Y-%m-%d").to_string())
+                        .unwrap_or_else(|| "never".to_string())
+                );
+            }
+        }
+        KeysCommand::Revoke { id } => {
+            store
+                .revoke_api_key(&id)
+                .await
+                .map_err(|err| err.to_string())?;
+            println!("Revoked key {}", id);
+        }
+    }
+
+    Ok(())
+}
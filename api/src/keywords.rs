@@ -0,0 +1,46 @@
+/// What an inbound message asks us to do with its sender.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeywordAction {
+    OptOut(String),
+    OptIn(String),
+}
+
+/// Opt-out and opt-in keywords, matched against the whole body of an inbound
+/// message, ignoring case, surrounding whitespace and trailing punctuation.
+#[derive(Clone, Debug)]
+pub struct Keywords {
+    opt_out: Vec<String>,
+    opt_in: Vec<String>,
+}
+
+impl Keywords {
+    pub fn new(opt_out: Vec<String>, opt_in: Vec<String>) -> Self {
+        let normalize = |words: Vec<String>| {
+            words
+                .into_iter()
+                .map(|word| word.trim().to_uppercase())
+                .filter(|word| !word.is_empty())
+                .collect()
+        };
+
+        Keywords {
+            opt_out: normalize(opt_out),
+            opt_in: normalize(opt_in),
+        }
+    }
+
+    pub fn classify(&self, body: &str) -> Option<KeywordAction> {
+        let word = body
+            .trim()
+            .trim_end_matches(|c: char| c.is_ascii_punctuation())
+            .to_uppercase();
+
+        if self.opt_out.contains(&word) {
+            Some(KeywordAction::OptOut(word))
+        } else if self.opt_in.contains(&word) {
+            Some(KeywordAction::OptIn(word))
+        } else {
+            None
+        }
+    }
+}
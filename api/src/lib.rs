@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use keywords::Keywords;
+use limits::{Limits, RateLimiter};
+use queue::publisher::Publisher;
+use store::Store;
+
+pub mod config;
+mod idempotency;
+pub mod keys;
+pub mod keywords;
+pub mod limits;
+pub mod routes;
+pub mod tls;
+mod webhooks;
+
+#[derive(Clone)]
+pub struct AppState {
+    /// Where accepted and received messages are published, normally RabbitMQ.
+    pub publisher: Arc<dyn Publisher>,
+    pub store: Store,
+    /// Shared secret ClickSend callbacks must present; webhooks are refused
+    /// when unset.
+    pub webhook_secret: Option<String>,
+    pub keywords: Keywords,
+    pub limits: Limits,
+    pub rate_limiter: RateLimiter,
+    /// How long `Idempotency-Key`s are remembered.
+    pub idempotency_window: chrono::Duration,
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use store::{QuotaLimits, QuotaUsage};
+
+use crate::config::LimitsConfig;
+
+pub const QUOTA_DAILY_REMAINING: HeaderName = HeaderName::from_static("x-quota-daily-remaining");
+pub const QUOTA_MONTHLY_REMAINING: HeaderName =
+    HeaderName::from_static("x-quota-monthly-remaining");
+
+/// Per-key request rate and message quotas, and request size limits.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Requests per second each key's bucket refills at.
+    pub rate: f64,
+    /// Requests a key may make in a burst.
+    pub burst: f64,
+    pub quotas: QuotaLimits,
+    /// Largest number of messages accepted by a single `/send_sms/batch` call.
+    pub max_batch_size: usize,
+}
+
+impl Limits {
+    pub fn from_config(config: &LimitsConfig) -> Self {
+        Limits {
+            rate: config.rate_per_second,
+            burst: config.burst,
+            quotas: QuotaLimits {
+                daily: config.daily_quota,
+                monthly: config.monthly_quota,
+            },
+            max_batch_size: config.max_batch_size,
+        }
+    }
+
+    /// Remaining-quota headers for a key that has used `usage`. Unlimited
+    /// quotas get no header.
+    pub fn quota_headers(&self, usage: &QuotaUsage) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let remaining = [
+            (QUOTA_DAILY_REMAINING, self.quotas.daily, usage.daily),
+            (QUOTA_MONTHLY_REMAINING, self.quotas.monthly, usage.monthly),
+        ];
+        for (name, limit, used) in remaining {
+            if let Some(limit) = limit {
+                headers.insert(name, HeaderValue::from(limit.saturating_sub(used)));
+            }
+        }
+        headers
+    }
+
+    /// How long until every exhausted quota in `usage` resets.
+    pub fn quota_reset(&self, usage: &QuotaUsage, now: DateTime<Utc>) -> Duration {
+        let exhausted = |used: u32, limit: Option<u32>| limit.is_some_and(|limit| used >= limit);
+
+        let next_month = if now.month() == 12 {
+            NaiveDate::from_ymd_opt(now.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(now.year(), now.month() + 1, 1)
+        };
+        let reset = if exhausted(usage.monthly, self.quotas.monthly) {
+            next_month
+        } else {
+            now.date_naive().succ_opt()
+        };
+
+        reset
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .and_then(|midnight| (midnight.and_utc() - now).to_std().ok())
+            .unwrap_or_default()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket rate limiter, with one bucket per API key.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        RateLimiter {
+            rate,
+            burst,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Takes a token from the key's bucket, or returns how long until one
+    /// will be available.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
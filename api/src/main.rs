@@ -1,72 +1,142 @@
-use std::{collections::HashSet, env, sync::Arc};
+use std::sync::Arc;
 
-use clap::Parser;
-use queue::publisher::RabbitMQ;
-use rand::{distributions::Alphanumeric, Rng};
+use api::{
+    config::{ApiConfig, ConfigArgs, LogFormat},
+    keys::{self, KeysCommand},
+    keywords::Keywords,
+    limits::{Limits, RateLimiter},
+    routes, tls, AppState,
+};
+use clap::{Parser, Subcommand};
+use queue::publisher::{QueueNames, RabbitMQ};
+use store::Store;
 use tokio::net::TcpListener;
-mod routes;
+use tracing::{info, warn};
+use tracing_subscriber::filter::LevelFilter;
 
 #[derive(Parser, Debug)]
 #[command(name = "API Server")]
-#[command(about = "Runs the API server or generates API keys", long_about = None)]
+#[command(about = "Runs the API server or manages API keys", long_about = None)]
 struct Cli {
-    /// Generate API Keys
-    #[arg(short, long)]
-    gen_keys: bool,
-}
+    #[command(flatten)]
+    config: ConfigArgs,
 
-fn generate_api_key() -> String {
-    let key_length = 32;
-    rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(key_length)
-        .map(char::from)
-        .collect()
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Clone)]
-pub struct AppState {
-    pub valid_api_keys: HashSet<String>,
-    pub rabbitmq: RabbitMQ,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the API server (the default)
+    Serve,
+    /// Manage API keys
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     let args = Cli::parse();
-    if args.gen_keys {
-        println!("Generating 5 new API keys:");
-        for i in 1..=5 {
-            let api_key = generate_api_key();
-            println!("API_KEY_{}=\"{}\"", i, api_key);
+    dotenv::dotenv().ok();
+
+    let config = match ApiConfig::load(&args.config) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let store = match Store::open(&config.store.path) {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("Failed to open message store: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(Command::Keys { command }) = args.command {
+        if let Err(err) = keys::run(&store, command).await {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
         }
         return;
     }
 
-    dotenv::dotenv().ok();
-    let mut valid_api_keys = HashSet::new();
+    // Validated when the config was loaded
+    let level = config.log_level().unwrap_or(LevelFilter::INFO);
+    let subscriber = tracing_subscriber::fmt().with_max_level(level);
+    match config.logging.format {
+        LogFormat::Full => subscriber.init(),
+        LogFormat::Compact => subscriber.compact().init(),
+    }
 
-    for (key, value) in env::vars() {
-        if key.starts_with("API_KEY_") {
-            valid_api_keys.insert(value);
-        }
+    // Keys used to be read from the environment; say so rather than leave
+    // their holders wondering why they're refused.
+    let legacy_keys: Vec<String> = std::env::vars()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with("API_KEY_"))
+        .collect();
+    if !legacy_keys.is_empty() {
+        warn!(
+            "Ignoring {}: API keys are no longer read from the environment, create them with `keys create`",
+            legacy_keys.join(", ")
+        );
     }
 
-    tracing_subscriber::fmt::init();
+    let tls = match config.server.tls.as_ref().map(tls::acceptor).transpose() {
+        Ok(tls) => tls,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let rabbitmq = match RabbitMQ::new("amqp://127.0.0.1:5672/%2f").await {
+    let queues = QueueNames {
+        outbound: config.amqp.outbound_queue.clone(),
+        inbound: config.amqp.inbound_queue.clone(),
+    };
+    let rabbitmq = match RabbitMQ::with_queues(&config.amqp.url, queues).await {
         Ok(connection) => connection,
         Err(err) => {
             eprintln!("Failed to initialize RabbitMQ: {:?}", err);
-            return;
+            std::process::exit(1);
         }
     };
+
+    let limits = Limits::from_config(&config.limits);
     let app_state = AppState {
-        valid_api_keys,
-        rabbitmq,
+        publisher: Arc::new(rabbitmq),
+        store,
+        webhook_secret: config.webhooks.secret.clone(),
+        keywords: Keywords::new(
+            config.keywords.opt_out.clone(),
+            config.keywords.opt_in.clone(),
+        ),
+        rate_limiter: RateLimiter::new(limits.rate, limits.burst),
+        limits,
+        idempotency_window: chrono::Duration::hours(config.limits.idempotency_window_hours),
     };
 
     let app = routes::app(app_state);
 
-    let listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let listener = match TcpListener::bind(config.server.bind).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Failed to bind {}: {}", config.server.bind, err);
+            std::process::exit(1);
+        }
+    };
+    info!(
+        "Listening on {} ({})",
+        config.server.bind,
+        if tls.is_some() { "https" } else { "http" }
+    );
+
+    match tls {
+        Some(acceptor) => tls::serve(listener, acceptor, app).await,
+        None => axum::serve(listener, app).await.unwrap(),
+    }
 }
@@ -1,78 +1,625 @@
-use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     body::Body,
-    extract::{rejection::JsonRejection, State},
-    http::{Request, StatusCode},
+    extract::{rejection::JsonRejection, Path, Query, State},
+    http::{header, HeaderMap, Request, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing, Json,
+    routing, Extension, Json,
 };
 use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
-use shared::{ApiResponse, SmsRequest};
+use chrono::Utc;
+use clicksend::validators::validate_e164;
+use serde::Deserialize;
+use shared::{
+    ApiResponse, BatchItemResult, BatchSmsRequest, BatchSmsResponse, ErrorCode,
+    InboundListResponse, MessageStatus, MessageStatusResponse, QueuedSms, SendSmsResponse,
+    SmsRequest, StatusChange, SUPPRESSED_MESSAGE,
+};
+use store::{ApiKeyRecord, MessageRecord, QuotaUsage, Reservation, Scope, StatusUpdate};
+use tracing::{error, warn};
+
+use crate::{idempotency, webhooks, AppState};
+
+/// Most inbound messages returned by a single `/inbound` call.
+const MAX_INBOUND_LIMIT: u32 = 500;
+
+pub(crate) fn api_error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ApiResponse {
+            status: status.as_u16().into(),
+            message: message.to_string(),
+            code: None,
+        }),
+    )
+        .into_response()
+}
+
+fn too_many_requests(message: &str, code: ErrorCode, retry_after: Duration) -> Response {
+    // Round up, so a client that waits exactly that long isn't refused again
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        Json(ApiResponse {
+            status: 429,
+            message: message.to_string(),
+            code: Some(code),
+        }),
+    )
+        .into_response()
+}
+
+fn quota_exceeded(app_state: &AppState, usage: &QuotaUsage, message: &str) -> Response {
+    let limits = &app_state.limits;
+    (
+        limits.quota_headers(usage),
+        too_many_requests(
+            message,
+            ErrorCode::QuotaExceeded,
+            limits.quota_reset(usage, Utc::now()),
+        ),
+    )
+        .into_response()
+}
+
+/// Counts `count` messages against the caller's quota, returning the
+/// remaining-quota headers for the response, or the response refusing them.
+async fn reserve_quota(
+    app_state: &AppState,
+    key: &ApiKeyRecord,
+    count: usize,
+) -> Result<HeaderMap, Response> {
+    let count = u32::try_from(count).unwrap_or(u32::MAX);
+    let limits = &app_state.limits;
+
+    match app_state
+        .store
+        .reserve_quota(&key.id, count, limits.quotas, Utc::now())
+        .await
+    {
+        Ok(Reservation::Reserved(usage)) => Ok(limits.quota_headers(&usage)),
+        Ok(Reservation::Exceeded(usage)) => Err(quota_exceeded(
+            app_state,
+            &usage,
+            "Not enough message quota remaining",
+        )),
+        Err(err) => {
+            error!("Failed to reserve quota for {}: {}", key.id, err);
+            Err(api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check message quota",
+            ))
+        }
+    }
+}
 
-use crate::AppState;
+fn suppressed_error() -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(ApiResponse {
+            status: 422,
+            message: SUPPRESSED_MESSAGE.to_string(),
+            code: Some(ErrorCode::RecipientSuppressed),
+        }),
+    )
+        .into_response()
+}
 
 pub async fn send_sms(
     State(app_state): State<AppState>,
+    Extension(key): Extension<ApiKeyRecord>,
+    headers: HeaderMap,
     result: Result<Json<SmsRequest>, JsonRejection>,
-) -> impl IntoResponse {
-    if let Err(_) = result {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                status: 500,
-                message: "Malformed request".to_string(),
-            }),
-        );
-    }
+) -> Response {
+    let Ok(Json(payload)) = result else {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, "Malformed request");
+    };
 
-    let Json(payload) = result.unwrap();
+    let request_hash = idempotency::request_hash(&payload);
+    idempotency::idempotent(&app_state, &key, &headers, &request_hash, || {
+        queue_sms(&app_state, &key, payload)
+    })
+    .await
+}
 
+async fn queue_sms(app_state: &AppState, key: &ApiKeyRecord, payload: SmsRequest) -> Response {
     let sms_message = SmsRequest {
         phone_number: payload.phone_number,
         message: payload.message,
+        send_at: payload.send_at,
     };
 
-    match app_state.rabbitmq.publish_message(sms_message).await {
+    // Checked up front, as a scheduled message would only fail once it's due
+    if let Err(err) = validate_e164(&sms_message.phone_number) {
+        return api_error(StatusCode::BAD_REQUEST, &err.to_string());
+    }
+    if let Err(message) = validate_send_at(&sms_message) {
+        return api_error(StatusCode::BAD_REQUEST, message);
+    }
+
+    match app_state
+        .store
+        .is_suppressed(&sms_message.phone_number)
+        .await
+    {
+        Ok(false) => {}
+        Ok(true) => return suppressed_error(),
+        Err(err) => {
+            error!("Failed to check suppression list: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store the message",
+            );
+        }
+    }
+
+    let quota_headers = match reserve_quota(app_state, key, 1).await {
+        Ok(headers) => headers,
+        Err(response) => return response,
+    };
+
+    let record = match app_state.store.insert_message(&sms_message, &key.id).await {
+        Ok(record) => record,
+        Err(err) => {
+            error!("Failed to store message: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store the message",
+            );
+        }
+    };
+
+    if record.status == MessageStatus::Scheduled {
+        return (
+            quota_headers,
+            Json(SendSmsResponse {
+                status: 200,
+                message: "Message scheduled".to_string(),
+                id: record.id,
+            }),
+        )
+            .into_response();
+    }
+
+    let queued = QueuedSms {
+        id: record.id.clone(),
+        request: sms_message,
+    };
+    let published = app_state.publisher.publish_message(queued).await;
+
+    let (status, error) = match &published {
+        Ok(_) => (MessageStatus::Queued, None),
+        Err(err) => (MessageStatus::Failed, Some(err.to_string())),
+    };
+    if let Err(err) = app_state
+        .store
+        .set_status(&record.id, status, error.as_deref())
+        .await
+    {
+        error!("Failed to update status of message {}: {}", record.id, err);
+    }
+
+    match published {
         Ok(_) => (
             StatusCode::OK,
-            Json(ApiResponse {
+            quota_headers,
+            Json(SendSmsResponse {
                 status: 200,
                 message: "Message queued".to_string(),
+                id: record.id,
             }),
-        ),
-        Err(_) => (
+        )
+            .into_response(),
+        Err(queue::AppError::Unavailable(_)) => (
+            [(header::RETRY_AFTER, "5")],
+            api_error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Message queue unavailable, try again later",
+            ),
+        )
+            .into_response(),
+        Err(_) => api_error(
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                status: 500,
-                message: "Failed to queue the message".to_string(),
-            }),
+            "Failed to queue the message",
+        ),
+    }
+}
+
+pub async fn send_sms_batch(
+    State(app_state): State<AppState>,
+    Extension(key): Extension<ApiKeyRecord>,
+    result: Result<Json<BatchSmsRequest>, JsonRejection>,
+) -> Response {
+    let Ok(Json(batch)) = result else {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, "Malformed request");
+    };
+
+    let requests = batch.into_requests();
+    if requests.is_empty() {
+        return api_error(StatusCode::BAD_REQUEST, "Batch contains no messages");
+    }
+    let max_batch_size = app_state.limits.max_batch_size;
+    if requests.len() > max_batch_size {
+        return api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("Batch exceeds {} messages", max_batch_size),
+        );
+    }
+
+    let mut results: Vec<BatchItemResult> = requests
+        .iter()
+        .map(|sms| BatchItemResult {
+            phone_number: sms.phone_number.clone(),
+            accepted: false,
+            id: None,
+            error: None,
+        })
+        .collect();
+
+    let phone_numbers = requests
+        .iter()
+        .map(|sms| sms.phone_number.clone())
+        .collect();
+    let suppressed = match app_state.store.suppressed_among(phone_numbers).await {
+        Ok(suppressed) => suppressed,
+        Err(err) => {
+            error!("Failed to check suppression list: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store the messages",
+            );
+        }
+    };
+
+    let mut indices = Vec::new();
+    let mut valid = Vec::new();
+    for (index, sms) in requests.into_iter().enumerate() {
+        if suppressed.contains(&sms.phone_number) {
+            results[index].error = Some(SUPPRESSED_MESSAGE.to_string());
+            continue;
+        }
+        if let Err(err) = validate_e164(&sms.phone_number) {
+            results[index].error = Some(err.to_string());
+            continue;
+        }
+        if let Err(message) = validate_send_at(&sms) {
+            results[index].error = Some(message.to_string());
+            continue;
+        }
+        indices.push(index);
+        valid.push(sms);
+    }
+
+    let quota_headers = match reserve_quota(&app_state, &key, valid.len()).await {
+        Ok(headers) => headers,
+        Err(response) => return response,
+    };
+
+    let records = match app_state.store.insert_messages(&valid, &key.id).await {
+        Ok(records) => records,
+        Err(err) => {
+            error!("Failed to store batch: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store the messages",
+            );
+        }
+    };
+
+    // Scheduled messages are done with for now: the worker's scheduler
+    // publishes them once they are due.
+    let mut queued = Vec::new();
+    let mut queued_indices = Vec::new();
+    for ((index, record), sms) in indices.into_iter().zip(records).zip(valid) {
+        if record.status == MessageStatus::Scheduled {
+            results[index].accepted = true;
+            results[index].id = Some(record.id);
+            continue;
+        }
+        queued_indices.push(index);
+        queued.push(QueuedSms {
+            id: record.id,
+            request: sms,
+        });
+    }
+    let published = app_state.publisher.publish_messages(&queued).await;
+
+    let mut updates = Vec::with_capacity(queued.len());
+    for ((index, sms), outcome) in queued_indices.into_iter().zip(queued).zip(published) {
+        let item = &mut results[index];
+        match outcome {
+            Ok(()) => {
+                item.accepted = true;
+                item.id = Some(sms.id.clone());
+                updates.push(StatusUpdate {
+                    id: sms.id,
+                    status: MessageStatus::Queued,
+                    error: None,
+                });
+            }
+            Err(err) => {
+                item.error = Some("Failed to queue the message".to_string());
+                updates.push(StatusUpdate {
+                    id: sms.id,
+                    status: MessageStatus::Failed,
+                    error: Some(err.to_string()),
+                });
+            }
+        }
+    }
+    if let Err(err) = app_state.store.set_statuses(updates).await {
+        error!("Failed to update statuses of batch: {}", err);
+    }
+
+    let accepted = results.iter().filter(|item| item.accepted).count();
+    (
+        quota_headers,
+        Json(BatchSmsResponse {
+            status: 200,
+            accepted,
+            rejected: results.len() - accepted,
+            results,
+        }),
+    )
+        .into_response()
+}
+
+/// Loads a message the caller may see: one sent with their key, or any
+/// message for admin keys. Other keys' messages are reported as not found.
+async fn owned_message(
+    app_state: &AppState,
+    key: &ApiKeyRecord,
+    id: &str,
+) -> Result<MessageRecord, Response> {
+    match app_state.store.get_message(id).await {
+        Ok(Some(record))
+            if key.allows(Scope::Admin) || record.api_key_id.as_deref() == Some(&key.id) =>
+        {
+            Ok(record)
+        }
+        Ok(_) => Err(api_error(StatusCode::NOT_FOUND, "Message not found")),
+        Err(err) => {
+            error!("Failed to load message {}: {}", id, err);
+            Err(api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load the message",
+            ))
+        }
+    }
+}
+
+pub async fn get_message(
+    State(app_state): State<AppState>,
+    Extension(key): Extension<ApiKeyRecord>,
+    Path(id): Path<String>,
+) -> Response {
+    let record = match owned_message(&app_state, &key, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+
+    let history = match app_state.store.message_events(&id).await {
+        Ok(events) => events
+            .into_iter()
+            .map(|event| StatusChange {
+                status: event.status,
+                error: event.error,
+                at: event.created_at,
+            })
+            .collect(),
+        Err(err) => {
+            error!("Failed to load history of message {}: {}", id, err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load the message",
+            );
+        }
+    };
+
+    Json(MessageStatusResponse {
+        id: record.id,
+        phone_number: record.phone_number,
+        status: record.status,
+        send_at: record.send_at,
+        clicksend_message_id: record.clicksend_message_id,
+        error: record.last_error,
+        error_code: record.error_code,
+        created_at: record.created_at,
+        updated_at: record.updated_at,
+        history,
+    })
+    .into_response()
+}
+
+pub async fn cancel_message(
+    State(app_state): State<AppState>,
+    Extension(key): Extension<ApiKeyRecord>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(response) = owned_message(&app_state, &key, &id).await {
+        return response;
+    }
+
+    match app_state.store.cancel_scheduled(&id).await {
+        Ok(true) => Json(ApiResponse {
+            status: 200,
+            message: "Message cancelled".to_string(),
+            code: None,
+        })
+        .into_response(),
+        Ok(false) => api_error(
+            StatusCode::CONFLICT,
+            "Only scheduled messages that haven't been sent can be cancelled",
         ),
+        Err(store::AppError::NotFound(_)) => api_error(StatusCode::NOT_FOUND, "Message not found"),
+        Err(err) => {
+            error!("Failed to cancel message {}: {}", id, err);
+            api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to cancel the message",
+            )
+        }
+    }
+}
+
+/// A `send_at` time, if given, must be in the future.
+fn validate_send_at(sms: &SmsRequest) -> Result<(), &'static str> {
+    match sms.send_at {
+        Some(send_at) if send_at <= Utc::now() => Err("send_at must be in the future"),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InboundQuery {
+    limit: Option<u32>,
+}
+
+/// Replies to the caller's messages; admin keys see every inbound message.
+pub async fn list_inbound(
+    State(app_state): State<AppState>,
+    Extension(key): Extension<ApiKeyRecord>,
+    Query(query): Query<InboundQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(50).min(MAX_INBOUND_LIMIT);
+    let owner = (!key.allows(Scope::Admin)).then_some(key.id.as_str());
+
+    match app_state.store.list_inbound(owner, limit).await {
+        Ok(messages) => Json(InboundListResponse {
+            status: 200,
+            messages,
+        })
+        .into_response(),
+        Err(err) => {
+            error!("Failed to list inbound messages: {}", err);
+            api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load inbound messages",
+            )
+        }
     }
 }
 
 pub fn app(app_state: AppState) -> axum::Router {
-    axum::Router::new()
+    let send = axum::Router::new()
+        .route("/send_sms/batch", routing::post(send_sms_batch))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            quota_middleware,
+        ))
+        // Not refused up front: a retry of a request that used the last of
+        // the quota must still get its original response replayed
         .route("/send_sms", routing::post(send_sms))
-        .route_layer(middleware::from_fn(auth_middleware))
+        .route("/messages/:id", routing::delete(cancel_message))
+        .route_layer(middleware::from_fn(require_send));
+
+    let read = axum::Router::new()
+        .route("/messages/:id", routing::get(get_message))
+        .route("/inbound", routing::get(list_inbound))
+        .route_layer(middleware::from_fn(require_read_status));
+
+    send.merge(read)
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth_middleware,
+        ))
+        // Webhooks authenticate with a shared secret instead of an API key
+        .route(
+            "/webhooks/clicksend/delivery",
+            routing::post(webhooks::delivery_receipt),
+        )
+        .route(
+            "/webhooks/clicksend/inbound",
+            routing::post(webhooks::inbound_sms),
+        )
         .with_state(app_state)
 }
 
-async fn auth_middleware<B>(
-    req: Request<Body>,
+async fn auth_middleware(
+    State(state): State<AppState>,
+    TypedHeader(Authorization(bearer)): TypedHeader<Authorization<Bearer>>,
+    mut req: Request<Body>,
     next: Next,
 ) -> Response {
-    State(state): State<Arc<AppState>>,
-    TypedHeader(Authorization(bearer)): TypedHeader<Authorization<Bearer>>,
-    let token = bearer.token();
+    let key = match state.store.authenticate(bearer.token()).await {
+        Ok(Some(key)) => key,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
+        Err(err) => {
+            error!("Failed to authenticate API key: {}", err);
+            return api_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to authenticate");
+        }
+    };
+
+    if let Err(retry_after) = state.rate_limiter.check(&key.id) {
+        warn!("Rate limited key {} ({})", key.id, key.name);
+        let quota_headers = match state.store.quota_usage(&key.id, Utc::now()).await {
+            Ok(usage) => state.limits.quota_headers(&usage),
+            Err(err) => {
+                error!("Failed to load quota usage for {}: {}", key.id, err);
+                HeaderMap::new()
+            }
+        };
+        return (
+            quota_headers,
+            too_many_requests("Rate limit exceeded", ErrorCode::RateLimited, retry_after),
+        )
+            .into_response();
+    }
 
-    if !state.valid_api_keys.contains(token) {
-        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    req.extensions_mut().insert(key);
+    next.run(req).await
+}
+
+async fn require_send(req: Request<Body>, next: Next) -> Response {
+    require_scope(Scope::Send, req, next).await
+}
+
+async fn require_read_status(req: Request<Body>, next: Next) -> Response {
+    require_scope(Scope::ReadStatus, req, next).await
+}
+
+/// Refuses callers whose key (added by [`auth_middleware`]) lacks `scope`.
+async fn require_scope(scope: Scope, req: Request<Body>, next: Next) -> Response {
+    let allowed = req
+        .extensions()
+        .get::<ApiKeyRecord>()
+        .is_some_and(|key| key.allows(scope));
+
+    if !allowed {
+        return api_error(
+            StatusCode::FORBIDDEN,
+            &format!("API key lacks the {} scope", scope),
+        );
     }
 
     next.run(req).await
 }
+
+/// Refuses sends outright once the caller's quota is used up. Handlers still
+/// reserve the exact number of messages they accept.
+async fn quota_middleware(
+    State(state): State<AppState>,
+    Extension(key): Extension<ApiKeyRecord>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    match state.store.quota_usage(&key.id, Utc::now()).await {
+        Ok(usage) if !usage.allows(1, &state.limits.quotas) => {
+            quota_exceeded(&state, &usage, "Message quota exceeded")
+        }
+        Ok(_) => next.run(req).await,
+        Err(err) => {
+            error!("Failed to load quota usage for {}: {}", key.id, err);
+            api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check message quota",
+            )
+        }
+    }
+}
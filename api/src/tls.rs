@@ -0,0 +1,71 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use axum::Router;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use tokio::net::TcpListener;
+use tokio_rustls::{
+    rustls::{crypto::ring, ServerConfig},
+    TlsAcceptor,
+};
+use tracing::warn;
+
+use crate::config::TlsConfig;
+
+/// Builds the TLS acceptor from the PEM certificate chain and private key.
+pub fn acceptor(config: &TlsConfig) -> Result<TlsAcceptor, String> {
+    let open = |path: &std::path::Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|err| format!("Failed to open {}: {}", path.display(), err))
+    };
+
+    let certs = rustls_pemfile::certs(&mut open(&config.cert_path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Invalid certificate: {}", err))?;
+    let key = rustls_pemfile::private_key(&mut open(&config.key_path)?)
+        .map_err(|err| format!("Invalid private key: {}", err))?
+        .ok_or_else(|| format!("No private key in {}", config.key_path.display()))?;
+
+    let server_config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|err| format!("Invalid TLS configuration: {}", err))?;
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// Serves `app` over HTTPS, handling each connection on its own task.
+pub async fn serve(listener: TcpListener, acceptor: TlsAcceptor, app: Router) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("Failed to accept connection: {}", err);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let service = TowerToHyperService::new(app.clone());
+
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("TLS handshake with {} failed: {}", peer, err);
+                    return;
+                }
+            };
+
+            if let Err(err) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                warn!("Connection from {} failed: {}", peer, err);
+            }
+        });
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use shared::{constant_time_eq, ApiResponse, MessageStatus};
+use store::{NewInbound, Received};
+use tracing::{error, info, warn};
+
+use crate::{keywords::KeywordAction, routes::api_error, AppState};
+
+/// Header ClickSend callbacks can carry the shared secret in. The secret may
+/// also be given as a `secret` query parameter, since that's all ClickSend's
+/// callback URL settings allow.
+const SECRET_HEADER: &str = "x-webhook-secret";
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookQuery {
+    secret: Option<String>,
+}
+
+/// A ClickSend delivery report, accepted as either JSON or form data.
+#[derive(Debug, Default)]
+pub struct DeliveryReceipt {
+    pub message_id: Option<String>,
+    pub status: Option<String>,
+    pub status_code: Option<String>,
+    pub status_text: Option<String>,
+    pub error_code: Option<String>,
+    pub error_text: Option<String>,
+    pub custom_string: Option<String>,
+}
+
+impl DeliveryReceipt {
+    fn from_fields(mut fields: HashMap<String, String>) -> Self {
+        let mut take = |key: &str| fields.remove(key).filter(|value| !value.is_empty());
+
+        DeliveryReceipt {
+            message_id: take("message_id"),
+            status: take("status"),
+            status_code: take("status_code"),
+            status_text: take("status_text"),
+            error_code: take("error_code"),
+            error_text: take("error_text"),
+            custom_string: take("custom_string"),
+        }
+    }
+
+    /// ClickSend reports a delivered message with status `Delivered` and
+    /// status code 201; anything else is a failure.
+    fn delivered(&self) -> bool {
+        self.status
+            .as_deref()
+            .is_some_and(|status| status.eq_ignore_ascii_case("delivered"))
+            || self.status_code.as_deref() == Some("201")
+    }
+}
+
+pub async fn delivery_receipt(
+    State(app_state): State<AppState>,
+    Query(query): Query<WebhookQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !authorized(&app_state, &headers, &query) {
+        return api_error(StatusCode::UNAUTHORIZED, "Unauthorized");
+    }
+
+    let Some(fields) = parse_fields(&headers, &body) else {
+        return api_error(StatusCode::BAD_REQUEST, "Malformed delivery receipt");
+    };
+    let receipt = DeliveryReceipt::from_fields(fields);
+
+    let record = match app_state
+        .store
+        .find_by_clicksend_reference(
+            receipt.message_id.as_deref(),
+            receipt.custom_string.as_deref(),
+        )
+        .await
+    {
+        Ok(Some(record)) => record,
+        Ok(None) => {
+            // Acknowledge anyway so ClickSend doesn't keep retrying a receipt
+            // we'll never be able to match.
+            warn!(
+                "Delivery receipt for unknown ClickSend message {:?}",
+                receipt.message_id
+            );
+            return acknowledge("Receipt ignored: unknown message");
+        }
+        Err(err) => {
+            error!("Failed to look up delivery receipt: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to record the receipt",
+            );
+        }
+    };
+
+    let (status, error_code, error) = if receipt.delivered() {
+        (MessageStatus::Delivered, None, None)
+    } else {
+        (
+            MessageStatus::Undelivered,
+            receipt
+                .error_code
+                .as_deref()
+                .or(receipt.status_code.as_deref()),
+            receipt
+                .error_text
+                .as_deref()
+                .or(receipt.status_text.as_deref()),
+        )
+    };
+
+    if let Err(err) = app_state
+        .store
+        .record_delivery(&record.id, status, error_code, error)
+        .await
+    {
+        error!(
+            "Failed to record delivery of message {}: {}",
+            record.id, err
+        );
+        return api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to record the receipt",
+        );
+    }
+
+    info!("Message {} is {}", record.id, status);
+    acknowledge("Receipt recorded")
+}
+
+pub async fn inbound_sms(
+    State(app_state): State<AppState>,
+    Query(query): Query<WebhookQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !authorized(&app_state, &headers, &query) {
+        return api_error(StatusCode::UNAUTHORIZED, "Unauthorized");
+    }
+
+    let Some(mut fields) = parse_fields(&headers, &body) else {
+        return api_error(StatusCode::BAD_REQUEST, "Malformed inbound message");
+    };
+    let mut take = |key: &str| fields.remove(key).filter(|value| !value.is_empty());
+
+    let (Some(from), Some(message_body)) = (take("from"), take("body")) else {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            "Inbound message is missing from or body",
+        );
+    };
+    let to = take("to").unwrap_or_default();
+    let clicksend_message_id = take("message_id");
+    let original_message_id = take("original_message_id");
+    let custom_string = take("custom_string");
+
+    let keyword_result = match app_state.keywords.classify(&message_body) {
+        Some(KeywordAction::OptOut(keyword)) => {
+            info!("{} opted out with {}", from, keyword);
+            app_state.store.suppress(&from, &keyword).await
+        }
+        Some(KeywordAction::OptIn(keyword)) => {
+            info!("{} opted back in with {}", from, keyword);
+            app_state.store.unsuppress(&from).await
+        }
+        None => Ok(()),
+    };
+    if let Err(err) = keyword_result {
+        // Opt-outs are a legal obligation: have ClickSend retry rather than
+        // silently miss one. Nothing is stored or published yet, so the
+        // retry doesn't duplicate the message.
+        error!("Failed to update suppression of {}: {}", from, err);
+        return api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to process the message",
+        );
+    }
+
+    // Link replies to the message they answer, when ClickSend tells us which
+    let reply_to = match app_state
+        .store
+        .find_by_clicksend_reference(original_message_id.as_deref(), custom_string.as_deref())
+        .await
+    {
+        Ok(record) => record.map(|record| record.id),
+        Err(err) => {
+            error!(
+                "Failed to match inbound message to an outbound one: {}",
+                err
+            );
+            None
+        }
+    };
+
+    let inbound = match app_state
+        .store
+        .insert_inbound(NewInbound {
+            from,
+            to,
+            body: message_body,
+            clicksend_message_id,
+            reply_to,
+        })
+        .await
+    {
+        Ok(Received::New(inbound)) => inbound,
+        Ok(Received::Duplicate(inbound)) => {
+            // ClickSend redelivered a callback we already handled
+            info!("Ignoring repeat of inbound message {}", inbound.id);
+            return acknowledge("Message already received");
+        }
+        Err(err) => {
+            error!("Failed to store inbound message: {}", err);
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store the message",
+            );
+        }
+    };
+
+    // The message is stored either way; a failed publish only affects
+    // downstream consumers, so don't make ClickSend resend it.
+    if let Err(err) = app_state.publisher.publish_inbound(&inbound).await {
+        error!("Failed to publish inbound message {}: {}", inbound.id, err);
+    }
+
+    info!(
+        "Received inbound message {} from {}",
+        inbound.id, inbound.from
+    );
+
+    acknowledge("Message received")
+}
+
+pub(crate) fn authorized(app_state: &AppState, headers: &HeaderMap, query: &WebhookQuery) -> bool {
+    let Some(expected) = app_state.webhook_secret.as_deref() else {
+        return false;
+    };
+
+    let provided = headers
+        .get(SECRET_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or(query.secret.as_deref());
+
+    provided.is_some_and(|provided| constant_time_eq(provided.as_bytes(), expected.as_bytes()))
+}
+
+/// Reads a callback body into flat string fields, from JSON when the content
+/// type says so and from form data otherwise.
+pub(crate) fn parse_fields(headers: &HeaderMap, body: &[u8]) -> Option<HashMap<String, String>> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    if !is_json {
+        return serde_urlencoded::from_bytes(body).ok();
+    }
+
+    let Value::Object(object) = serde_json::from_slice(body).ok()? else {
+        return None;
+    };
+    Some(
+        object
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let value = match value {
+                    Value::String(value) => value,
+                    Value::Number(value) => value.to_string(),
+                    Value::Bool(value) => value.to_string(),
+                    _ => return None,
+                };
+                Some((key, value))
+            })
+            .collect(),
+    )
+}
+
+fn acknowledge(message: &str) -> Response {
+    (
+        StatusCode::OK,
+        Json(ApiResponse {
+            status: 200,
+            message: message.to_string(),
+            code: None,
+        }),
+    )
+        .into_response()
+}
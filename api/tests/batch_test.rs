@@ -0,0 +1,95 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{TestApp, MAX_BATCH_SIZE};
+use serde_json::json;
+use shared::{MessageStatus, SUPPRESSED_MESSAGE};
+use store::Scope;
+
+#[tokio::test]
+async fn test_batch_reports_each_message() {
+    let app = TestApp::new();
+    let token = app.key(&[Scope::Send]).await;
+    app.store.suppress("+61400000003", "STOP").await.unwrap();
+    let send_at = Utc::now() + Duration::hours(1);
+
+    let response = app
+        .post_json(
+            "/send_sms/batch",
+            &token,
+            json!({ "messages": [
+                { "phone_number": "+61400000001", "message": "Now" },
+                { "phone_number": "0400000002", "message": "Not E.164" },
+                { "phone_number": "+61400000003", "message": "Opted out" },
+                { "phone_number": "+61400000004", "message": "Later", "send_at": send_at },
+            ] }),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["accepted"], 2);
+    assert_eq!(response.body["rejected"], 2);
+    let results = response.body["results"].as_array().unwrap();
+    let accepted: Vec<_> = results
+        .iter()
+        .map(|item| item["accepted"].clone())
+        .collect();
+    assert_eq!(accepted, [true, false, false, true]);
+    assert!(results[1]["error"].is_string());
+    assert_eq!(results[2]["error"], SUPPRESSED_MESSAGE);
+
+    // Only the immediate message is published; the scheduled one waits
+    let immediate = results[0]["id"].as_str().unwrap();
+    assert_eq!(*app.publisher.messages.lock().unwrap(), [immediate]);
+    let scheduled = results[3]["id"].as_str().unwrap();
+    let record = app.store.get_message(scheduled).await.unwrap().unwrap();
+    assert_eq!(record.status, MessageStatus::Scheduled);
+}
+
+#[tokio::test]
+async fn test_broadcast_sends_one_message_to_each_number() {
+    let app = TestApp::new();
+    let token = app.key(&[Scope::Send]).await;
+
+    let response = app
+        .post_json(
+            "/send_sms/batch",
+            &token,
+            json!({
+                "message": "Hello all",
+                "phone_numbers": ["+61400000001", "+61400000002"],
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["accepted"], 2);
+    assert_eq!(app.publisher.messages.lock().unwrap().len(), 2);
+    let id = response.body["results"][1]["id"].as_str().unwrap();
+    let record = app.store.get_message(id).await.unwrap().unwrap();
+    assert_eq!(record.phone_number, "+61400000002");
+    assert_eq!(record.message, "Hello all");
+}
+
+#[tokio::test]
+async fn test_empty_and_oversized_batches_are_refused() {
+    let app = TestApp::new();
+    let token = app.key(&[Scope::Send]).await;
+
+    let empty = app
+        .post_json("/send_sms/batch", &token, json!({ "messages": [] }))
+        .await;
+    assert_eq!(empty.status, StatusCode::BAD_REQUEST);
+
+    let phone_numbers = vec!["+61400000001"; MAX_BATCH_SIZE + 1];
+    let oversized = app
+        .post_json(
+            "/send_sms/batch",
+            &token,
+            json!({ "message": "Hello", "phone_numbers": phone_numbers }),
+        )
+        .await;
+    assert_eq!(oversized.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(app.publisher.messages.lock().unwrap().is_empty());
+}
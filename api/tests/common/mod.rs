@@ -0,0 +1,181 @@
+//! A router over an in-memory store and a publisher that only records, for
+//! exercising the API end to end without a broker.
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use api::{
+    keywords::Keywords,
+    limits::{Limits, RateLimiter},
+    routes, AppState,
+};
+use axum::{
+    body::{self, Body},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use queue::{publisher::Publisher, AppResult};
+use serde_json::Value;
+use shared::{InboundSms, QueuedSms};
+use store::{NewApiKey, QuotaLimits, Scope, Store};
+use tower::ServiceExt;
+
+pub const WEBHOOK_SECRET: &str = "webhook-secret";
+
+/// Largest batch the test app accepts.
+pub const MAX_BATCH_SIZE: usize = 5;
+
+#[derive(Default)]
+pub struct RecordingPublisher {
+    /// IDs of the outbound messages published.
+    pub messages: Mutex<Vec<String>>,
+    pub inbound: Mutex<Vec<InboundSms>>,
+}
+
+#[async_trait::async_trait]
+impl Publisher for RecordingPublisher {
+    async fn publish_message(&self, sms: QueuedSms) -> AppResult<()> {
+        self.messages.lock().unwrap().push(sms.id);
+        Ok(())
+    }
+
+    async fn publish_inbound(&self, sms: &InboundSms) -> AppResult<()> {
+        self.inbound.lock().unwrap().push(sms.clone());
+        Ok(())
+    }
+
+    async fn publish_messages(&self, messages: &[QueuedSms]) -> Vec<AppResult<()>> {
+        let mut published = self.messages.lock().unwrap();
+        published.extend(messages.iter().map(|sms| sms.id.clone()));
+        messages.iter().map(|_| Ok(())).collect()
+    }
+}
+
+pub struct TestApp {
+    pub store: Store,
+    pub publisher: Arc<RecordingPublisher>,
+    router: Router,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        Self::with_quotas(QuotaLimits::default())
+    }
+
+    pub fn with_quotas(quotas: QuotaLimits) -> Self {
+        let store = Store::open_in_memory().unwrap();
+        let publisher = Arc::new(RecordingPublisher::default());
+        let limits = Limits {
+            rate: 1000.0,
+            burst: 1000.0,
+            quotas,
+            max_batch_size: MAX_BATCH_SIZE,
+        };
+        let router = routes::app(AppState {
+            publisher: publisher.clone(),
+            store: store.clone(),
+            webhook_secret: Some(WEBHOOK_SECRET.to_string()),
+            keywords: Keywords::new(vec!["STOP".to_string()], vec!["START".to_string()]),
+            rate_limiter: RateLimiter::new(limits.rate, limits.burst),
+            limits,
+            idempotency_window: chrono::Duration::hours(24),
+        });
+
+        TestApp {
+            store,
+            publisher,
+            router,
+        }
+    }
+
+    /// Creates a key with `scopes` and returns its token.
+    pub async fn key(&self, scopes: &[Scope]) -> String {
+        let (_, token) = self
+            .store
+            .create_api_key(NewApiKey {
+                name: "test".to_string(),
+                owner: "tests".to_string(),
+                scopes: scopes.to_vec(),
+                expires_at: None,
+            })
+            .await
+            .unwrap();
+        token
+    }
+
+    pub async fn get(&self, uri: &str, token: &str) -> TestResponse {
+        self.send(
+            authorized(Method::GET, uri, token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    pub async fn delete(&self, uri: &str, token: &str) -> TestResponse {
+        self.send(
+            authorized(Method::DELETE, uri, token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    pub async fn post_json(&self, uri: &str, token: &str, body: Value) -> TestResponse {
+        self.send(
+            authorized(Method::POST, uri, token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        TestResponse {
+            status,
+            headers,
+            body: serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        }
+    }
+}
+
+pub fn authorized(method: Method, uri: &str, token: &str) -> axum::http::request::Builder {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+}
+
+/// A ClickSend callback to `uri` with a JSON body.
+pub fn webhook_json(uri: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// A ClickSend callback to `uri` with a form body.
+pub fn webhook_form(uri: &str, fields: &[(&str, &str)]) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(serde_urlencoded::to_string(fields).unwrap()))
+        .unwrap()
+}
@@ -0,0 +1,83 @@
+use std::{env, fs, path::PathBuf};
+
+use api::config::{ApiConfig, ConfigArgs};
+use serial_test::serial;
+
+fn config_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn args(config: PathBuf) -> ConfigArgs {
+    ConfigArgs {
+        config: Some(config),
+        bind: None,
+        amqp_url: None,
+        database_path: None,
+        log_level: None,
+    }
+}
+
+#[test]
+#[serial]
+fn test_layers_override_in_order() {
+    let path = config_file(
+        "layering",
+        r#"
+        [server]
+        bind = "127.0.0.1:4000"
+
+        [store]
+        path = "file.db"
+
+        [logging]
+        level = "debug"
+        "#,
+    );
+    env::set_var("MESSAGING_STORE__PATH", "env.db");
+    env::set_var("MESSAGING_LOGGING__LEVEL", "warn");
+
+    let config = ApiConfig::load(&ConfigArgs {
+        log_level: Some("error".to_string()),
+        ..args(path.clone())
+    })
+    .unwrap();
+
+    env::remove_var("MESSAGING_STORE__PATH");
+    env::remove_var("MESSAGING_LOGGING__LEVEL");
+    fs::remove_file(path).unwrap();
+
+    // Default, untouched by any layer
+    assert_eq!(config.amqp.outbound_queue, "sms_queue");
+    // File over default
+    assert_eq!(config.server.bind.to_string(), "127.0.0.1:4000");
+    // Environment over file
+    assert_eq!(config.store.path, "env.db");
+    // Flag over environment
+    assert_eq!(config.logging.level, "error");
+}
+
+#[test]
+#[serial]
+fn test_validate_reports_every_problem() {
+    let path = config_file(
+        "invalid",
+        r#"
+        [amqp]
+        outbound_queue = "sms"
+        inbound_queue = "sms"
+
+        [limits]
+        burst = 0
+        idempotency_window_hours = 100000
+        "#,
+    );
+
+    let err = ApiConfig::load(&args(path.clone())).unwrap_err();
+    fs::remove_file(path).unwrap();
+
+    assert!(err.contains("outbound_queue and inbound_queue must differ"));
+    assert!(err.contains("limits.burst must be at least 1"));
+    assert!(err.contains("limits.idempotency_window_hours must be between 1 and 8760"));
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use common::{webhook_form, webhook_json, TestApp, WEBHOOK_SECRET};
+use serde_json::json;
+use shared::{MessageStatus, SmsRequest};
+use store::MessageRecord;
+
+async fn sent_message(app: &TestApp, clicksend_id: &str) -> MessageRecord {
+    let sms = SmsRequest {
+        phone_number: "+61400000000".to_string(),
+        message: "Test message".to_string(),
+        send_at: None,
+    };
+    let record = app.store.insert_message(&sms, "key").await.unwrap();
+    app.store
+        .mark_sent(&record.id, Some(clicksend_id))
+        .await
+        .unwrap();
+    record
+}
+
+async fn status(app: &TestApp, record: &MessageRecord) -> MessageRecord {
+    app.store.get_message(&record.id).await.unwrap().unwrap()
+}
+
+fn delivery_uri() -> String {
+    format!("/webhooks/clicksend/delivery?secret={}", WEBHOOK_SECRET)
+}
+
+#[tokio::test]
+async fn test_receipts_need_the_shared_secret() {
+    let app = TestApp::new();
+    let record = sent_message(&app, "CS-1").await;
+    let receipt = json!({ "message_id": "CS-1", "status": "Delivered" });
+
+    for uri in [
+        "/webhooks/clicksend/delivery",
+        "/webhooks/clicksend/delivery?secret=wrong",
+    ] {
+        let response = app.send(webhook_json(uri, receipt.clone())).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(status(&app, &record).await.status, MessageStatus::Sent);
+
+    let mut in_header = webhook_json("/webhooks/clicksend/delivery", receipt);
+    in_header
+        .headers_mut()
+        .insert("x-webhook-secret", WEBHOOK_SECRET.parse().unwrap());
+    assert_eq!(app.send(in_header).await.status, StatusCode::OK);
+    assert_eq!(status(&app, &record).await.status, MessageStatus::Delivered);
+}
+
+#[tokio::test]
+async fn test_receipts_are_recorded_from_json_and_form_bodies() {
+    let app = TestApp::new();
+    let delivered = sent_message(&app, "CS-1").await;
+    let failed = sent_message(&app, "CS-2").await;
+
+    let response = app
+        .send(webhook_json(
+            &delivery_uri(),
+            json!({ "message_id": "CS-1", "status_code": 201 }),
+        ))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        status(&app, &delivered).await.status,
+        MessageStatus::Delivered
+    );
+
+    let response = app
+        .send(webhook_form(
+            &delivery_uri(),
+            &[
+                ("message_id", "CS-2"),
+                ("status", "Undelivered"),
+                ("error_code", "301"),
+                ("error_text", "Delivery failure"),
+            ],
+        ))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let failed = status(&app, &failed).await;
+    assert_eq!(failed.status, MessageStatus::Undelivered);
+    assert_eq!(failed.error_code.as_deref(), Some("301"));
+    assert_eq!(failed.last_error.as_deref(), Some("Delivery failure"));
+}
+
+#[tokio::test]
+async fn test_unknown_and_malformed_receipts() {
+    let app = TestApp::new();
+
+    // Acknowledged, so ClickSend stops retrying a receipt that can't match
+    let response = app
+        .send(webhook_json(
+            &delivery_uri(),
+            json!({ "message_id": "CS-unknown", "status": "Delivered" }),
+        ))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["message"], "Receipt ignored: unknown message");
+
+    let malformed = Request::builder()
+        .method(Method::POST)
+        .uri(delivery_uri())
+        .header("content-type", "application/json")
+        .body(Body::from("not json"))
+        .unwrap();
+    assert_eq!(app.send(malformed).await.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, StatusCode};
+use common::{authorized, TestApp};
+use serde_json::json;
+use store::{QuotaLimits, Scope};
+
+#[tokio::test]
+async fn test_retry_after_using_up_the_quota_is_replayed() {
+    let app = TestApp::with_quotas(QuotaLimits {
+        daily: Some(1),
+        monthly: None,
+    });
+    let token = app.key(&[Scope::Send]).await;
+    let send = |key: &'static str| {
+        authorized(Method::POST, "/send_sms", &token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("idempotency-key", key)
+            .body(Body::from(
+                json!({ "phone_number": "+61400000000", "message": "Hello" }).to_string(),
+            ))
+            .unwrap()
+    };
+
+    let first = app.send(send("abc")).await;
+    assert_eq!(first.status, StatusCode::OK);
+    assert_eq!(first.headers["x-quota-daily-remaining"], "0");
+
+    // The first response was lost; the quota is used up, but this is the same request
+    let retry = app.send(send("abc")).await;
+    assert_eq!(retry.status, StatusCode::OK);
+    assert_eq!(retry.body, first.body);
+    assert_eq!(retry.headers["idempotent-replayed"], "true");
+    assert_eq!(retry.headers["x-quota-daily-remaining"], "0");
+    assert_eq!(app.publisher.messages.lock().unwrap().len(), 1);
+
+    let another = app.send(send("def")).await;
+    assert_eq!(another.status, StatusCode::TOO_MANY_REQUESTS);
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{webhook_form, TestApp, WEBHOOK_SECRET};
+use serde_json::json;
+use store::Scope;
+
+fn inbound_uri() -> String {
+    format!("/webhooks/clicksend/inbound?secret={}", WEBHOOK_SECRET)
+}
+
+/// Sends a message with `token` and marks it sent as `clicksend_id`.
+async fn sent_message(app: &TestApp, token: &str, clicksend_id: &str) -> String {
+    let response = app
+        .post_json(
+            "/send_sms",
+            token,
+            json!({ "phone_number": "+61400000000", "message": "Reply YES" }),
+        )
+        .await;
+    let id = response.body["id"].as_str().unwrap().to_string();
+    app.store.mark_sent(&id, Some(clicksend_id)).await.unwrap();
+    id
+}
+
+#[tokio::test]
+async fn test_inbound_is_stored_and_published_once() {
+    let app = TestApp::new();
+    let reply = [
+        ("from", "+61400000000"),
+        ("to", "+61411111111"),
+        ("body", "Yes please"),
+        ("message_id", "IN-1"),
+    ];
+
+    let unauthorized = app
+        .send(webhook_form("/webhooks/clicksend/inbound", &reply))
+        .await;
+    assert_eq!(unauthorized.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(webhook_form(&inbound_uri(), &reply)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["message"], "Message received");
+
+    // ClickSend retries until acknowledged; a repeat is acknowledged, not republished
+    let repeat = app.send(webhook_form(&inbound_uri(), &reply)).await;
+    assert_eq!(repeat.status, StatusCode::OK);
+    assert_eq!(repeat.body["message"], "Message already received");
+
+    let published = app.publisher.inbound.lock().unwrap();
+    assert_eq!(published.len(), 1);
+    assert_eq!(published[0].body, "Yes please");
+    assert_eq!(published[0].clicksend_message_id.as_deref(), Some("IN-1"));
+}
+
+#[tokio::test]
+async fn test_inbound_needs_a_sender_and_body() {
+    let app = TestApp::new();
+
+    let response = app
+        .send(webhook_form(&inbound_uri(), &[("from", "+61400000000")]))
+        .await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(app.publisher.inbound.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_keywords_suppress_and_unsuppress_the_sender() {
+    let app = TestApp::new();
+    let sender = "+61400000000";
+
+    let response = app
+        .send(webhook_form(
+            &inbound_uri(),
+            &[("from", sender), ("body", " stop "), ("message_id", "IN-1")],
+        ))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(app.store.is_suppressed(sender).await.unwrap());
+
+    app.send(webhook_form(
+        &inbound_uri(),
+        &[("from", sender), ("body", "START"), ("message_id", "IN-2")],
+    ))
+    .await;
+    assert!(!app.store.is_suppressed(sender).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_inbound_lists_only_replies_to_the_callers_messages() {
+    let app = TestApp::new();
+    let ours = app.key(&[Scope::Send, Scope::ReadStatus]).await;
+    let theirs = app.key(&[Scope::Send, Scope::ReadStatus]).await;
+    let admin = app.key(&[Scope::Admin]).await;
+    let our_message = sent_message(&app, &ours, "CS-1").await;
+    let their_message = sent_message(&app, &theirs, "CS-2").await;
+
+    // Replies are matched by ClickSend's ID or by our custom string
+    for (message_id, reference) in [
+        ("IN-1", ("original_message_id", "CS-1")),
+        ("IN-2", ("custom_string", their_message.as_str())),
+        ("IN-3", ("original_message_id", "CS-unknown")),
+    ] {
+        let response = app
+            .send(webhook_form(
+                &inbound_uri(),
+                &[
+                    ("from", "+61400000000"),
+                    ("body", "Yes"),
+                    ("message_id", message_id),
+                    reference,
+                ],
+            ))
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+
+    let response = app.get("/inbound", &ours).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let messages = response.body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["reply_to"], our_message.as_str());
+
+    let response = app.get("/inbound", &theirs).await;
+    let messages = response.body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["reply_to"], their_message.as_str());
+
+    let response = app.get("/inbound", &admin).await;
+    assert_eq!(response.body["messages"].as_array().unwrap().len(), 3);
+}
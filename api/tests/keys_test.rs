@@ -0,0 +1,20 @@
+use api::keys::{self, KeysCommand};
+use store::{Scope, Store};
+
+#[tokio::test]
+async fn test_expiry_out_of_range_is_refused() {
+    let store = Store::open_in_memory().unwrap();
+    let create = |days| KeysCommand::Create {
+        name: "test".to_string(),
+        owner: "tests".to_string(),
+        scopes: vec![Scope::Send],
+        expires_in_days: Some(days),
+    };
+
+    assert!(keys::run(&store, create(999_999_999_999)).await.is_err());
+    assert!(store.list_api_keys().await.unwrap().is_empty());
+
+    keys::run(&store, create(30)).await.unwrap();
+    let created = store.list_api_keys().await.unwrap();
+    assert!(created[0].expires_at.is_some());
+}
@@ -0,0 +1,50 @@
+use api::keywords::{KeywordAction, Keywords};
+
+fn keywords() -> Keywords {
+    Keywords::new(
+        vec!["stop".to_string(), " UNSUBSCRIBE ".to_string()],
+        vec!["START".to_string(), "STOP".to_string(), String::new()],
+    )
+}
+
+#[test]
+fn test_keywords_ignore_case_whitespace_and_punctuation() {
+    let keywords = keywords();
+
+    for body in [
+        "STOP",
+        "stop",
+        "  Stop\n",
+        "STOP!",
+        "stop.",
+        "Unsubscribe?!",
+    ] {
+        assert!(
+            matches!(keywords.classify(body), Some(KeywordAction::OptOut(_))),
+            "{:?} should opt out",
+            body
+        );
+    }
+    assert_eq!(
+        keywords.classify(" start. "),
+        Some(KeywordAction::OptIn("START".to_string()))
+    );
+}
+
+#[test]
+fn test_keywords_must_be_the_whole_message() {
+    let keywords = keywords();
+
+    for body in ["Please stop", "STOP IT", "!STOP", "", "STARTED"] {
+        assert_eq!(keywords.classify(body), None, "{:?} isn't a keyword", body);
+    }
+}
+
+#[test]
+fn test_opt_out_wins_over_opt_in() {
+    // STOP is configured as both; staying suppressed is the safe choice
+    assert_eq!(
+        keywords().classify("stop"),
+        Some(KeywordAction::OptOut("STOP".to_string()))
+    );
+}
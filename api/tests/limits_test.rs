@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use api::limits::{Limits, RateLimiter, QUOTA_DAILY_REMAINING, QUOTA_MONTHLY_REMAINING};
+use chrono::{DateTime, TimeZone, Utc};
+use store::{QuotaLimits, QuotaUsage};
+
+fn limits(daily: Option<u32>, monthly: Option<u32>) -> Limits {
+    Limits {
+        rate: 10.0,
+        burst: 20.0,
+        quotas: QuotaLimits { daily, monthly },
+        max_batch_size: 100,
+    }
+}
+
+fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+}
+
+#[test]
+fn test_rate_limiter_allows_a_burst_then_refills() {
+    let limiter = RateLimiter::new(50.0, 3.0);
+
+    for _ in 0..3 {
+        assert!(limiter.check("key-1").is_ok());
+    }
+    let retry_after = limiter.check("key-1").unwrap_err();
+    assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_millis(20));
+
+    // Each key has its own bucket
+    assert!(limiter.check("key-2").is_ok());
+
+    std::thread::sleep(Duration::from_millis(25));
+    assert!(limiter.check("key-1").is_ok());
+}
+
+#[test]
+fn test_daily_quota_resets_at_midnight() {
+    let usage = QuotaUsage {
+        daily: 100,
+        monthly: 100,
+    };
+
+    let reset = limits(Some(100), Some(1000)).quota_reset(&usage, at(2024, 6, 15, 12));
+
+    assert_eq!(reset, Duration::from_secs(12 * 3600));
+}
+
+#[test]
+fn test_monthly_quota_resets_on_the_first() {
+    let usage = QuotaUsage {
+        daily: 10,
+        monthly: 1000,
+    };
+    let limits = limits(Some(100), Some(1000));
+
+    assert_eq!(
+        limits.quota_reset(&usage, at(2024, 2, 28, 12)),
+        Duration::from_secs((24 + 12) * 3600)
+    );
+    // December rolls over into January of the next year
+    assert_eq!(
+        limits.quota_reset(&usage, at(2024, 12, 31, 23)),
+        Duration::from_secs(3600)
+    );
+    assert_eq!(
+        limits.quota_reset(&usage, at(2024, 12, 15, 0)),
+        Duration::from_secs(17 * 24 * 3600)
+    );
+}
+
+#[test]
+fn test_quota_headers_only_for_limited_quotas() {
+    let usage = QuotaUsage {
+        daily: 7,
+        monthly: 2000,
+    };
+
+    let headers = limits(Some(10), Some(1000)).quota_headers(&usage);
+    assert_eq!(headers[QUOTA_DAILY_REMAINING], "3");
+    assert_eq!(headers[QUOTA_MONTHLY_REMAINING], "0");
+
+    let headers = limits(None, Some(1000)).quota_headers(&usage);
+    assert!(!headers.contains_key(QUOTA_DAILY_REMAINING));
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::TestApp;
+use serde_json::json;
+use store::Scope;
+
+#[tokio::test]
+async fn test_sent_message_can_be_read_back() {
+    let app = TestApp::new();
+    let token = app.key(&[Scope::Send, Scope::ReadStatus]).await;
+
+    let sent = app
+        .post_json(
+            "/send_sms",
+            &token,
+            json!({ "phone_number": "+61400000000", "message": "Hello" }),
+        )
+        .await;
+    assert_eq!(sent.status, StatusCode::OK);
+    let id = sent.body["id"].as_str().unwrap();
+
+    let response = app.get(&format!("/messages/{}", id), &token).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["id"], id);
+    assert_eq!(response.body["phone_number"], "+61400000000");
+    assert_eq!(response.body["status"], "queued");
+    let history: Vec<_> = response.body["history"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|change| change["status"].clone())
+        .collect();
+    assert_eq!(history, [json!("accepted"), json!("queued")]);
+}
+
+#[tokio::test]
+async fn test_other_keys_cannot_see_or_cancel_a_message() {
+    let app = TestApp::new();
+    let owner = app.key(&[Scope::Send, Scope::ReadStatus]).await;
+    let other = app.key(&[Scope::Send, Scope::ReadStatus]).await;
+
+    let sent = app
+        .post_json(
+            "/send_sms",
+            &owner,
+            json!({
+                "phone_number": "+61400000000",
+                "message": "Later",
+                "send_at": Utc::now() + Duration::hours(1),
+            }),
+        )
+        .await;
+    let uri = format!("/messages/{}", sent.body["id"].as_str().unwrap());
+
+    // Indistinguishable from a message that doesn't exist
+    assert_eq!(app.get(&uri, &other).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.delete(&uri, &other).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        app.get("/messages/missing", &other).await.status,
+        StatusCode::NOT_FOUND
+    );
+
+    let response = app.get(&uri, &owner).await;
+    assert_eq!(response.body["status"], "scheduled");
+    assert_eq!(app.delete(&uri, &owner).await.status, StatusCode::OK);
+}
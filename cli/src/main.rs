@@ -1,19 +1,34 @@
-use clap::Parser;
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use colored::Colorize;
 use config::{Config, File};
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::Deserialize;
-use std::{path::PathBuf, time::Duration};
-use tokio;
+use shared::InboundListResponse;
+use std::{path::Path, time::Duration};
 
-use clicksend::{clicksend::ClickSendApi, AppResult, ClickSendClient};
+use clicksend::{
+    clicksend::client::OutboundSms,
+    provider::{twilio, ClickSendProvider, TwilioClient},
+    AppResult, ClickSendClient, SmsProvider,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "Message Sender")]
 #[command(author = "Shane Poppleton")]
 #[command(version = "1.0")]
-#[command(about = "Send SMS using ClickSend", long_about = None)]
+#[command(about = "Send SMS using ClickSend or Twilio", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// `cli -s .. -r .. -m ..` without a subcommand sends, as it always has
+    #[command(flatten)]
+    send: Option<SendArgs>,
+}
+
+#[derive(Args, Debug)]
+struct SendArgs {
     #[arg(short, long)]
     sender: String,
 
@@ -24,6 +39,18 @@ struct Cli {
     message: String,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send an SMS directly through the configured provider
+    Send(SendArgs),
+    /// List SMS received by the API server, newest first
+    Inbound {
+        /// How many messages to show
+        #[arg(short, long, default_value_t = 20)]
+        limit: u32,
+    },
+}
+
 #[derive(Debug, Deserialize)]
 struct ClickSendConfig {
     api_key: String,
@@ -32,13 +59,82 @@ struct ClickSendConfig {
     version: String,
 }
 
-impl ClickSendConfig {
-    fn from_file(path: &PathBuf) -> Result<Self, config::ConfigError> {
-        let config = Config::builder()
-            .add_source(File::from(path.as_path()))
-            .build()?;
+/// The `[twilio]` section of config.toml, used when `provider = "twilio"`.
+#[derive(Debug, Deserialize)]
+struct TwilioConfig {
+    account_sid: String,
+    auth_token: String,
+    #[serde(default = "default_twilio_base_url")]
+    base_url: String,
+}
+
+fn default_twilio_base_url() -> String {
+    twilio::DEFAULT_BASE_URL.to_string()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ProviderKind {
+    #[default]
+    ClickSend,
+    Twilio,
+}
+
+/// The `[server]` section of config.toml, needed by commands that talk to
+/// the API server rather than an SMS provider.
+#[derive(Debug, Deserialize)]
+struct ServerConfig {
+    url: String,
+    api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CliConfig {
+    /// Which provider `send` goes through; ClickSend unless set.
+    #[serde(default)]
+    provider: ProviderKind,
+    /// ClickSend's settings sit at the top level of config.toml.
+    #[serde(flatten)]
+    clicksend: Option<ClickSendConfig>,
+    twilio: Option<TwilioConfig>,
+    server: Option<ServerConfig>,
+}
+
+impl CliConfig {
+    fn from_file(path: &Path) -> Result<Self, config::ConfigError> {
+        let config = Config::builder().add_source(File::from(path)).build()?;
 
-        config.try_deserialize::<ClickSendConfig>()
+        config.try_deserialize::<CliConfig>()
+    }
+
+    /// Builds the client for the configured provider.
+    fn sms_provider(&self) -> Result<Box<dyn SmsProvider>, String> {
+        match self.provider {
+            ProviderKind::ClickSend => {
+                let config = self
+                    .clicksend
+                    .as_ref()
+                    .ok_or("api_key, username, base_url and version are required for ClickSend")?;
+                let client = ClickSendClient::new(
+                    &config.api_key,
+                    &config.username,
+                    &config.base_url,
+                    &config.version,
+                )
+                .map_err(|err| err.to_string())?;
+                Ok(Box::new(ClickSendProvider::new(client)))
+            }
+            ProviderKind::Twilio => {
+                let config = self
+                    .twilio
+                    .as_ref()
+                    .ok_or("a [twilio] section is required for Twilio")?;
+                let client =
+                    TwilioClient::new(&config.account_sid, &config.auth_token, &config.base_url)
+                        .map_err(|err| err.to_string())?;
+                Ok(Box::new(client))
+            }
+        }
     }
 }
 
@@ -58,16 +154,52 @@ async fn main() -> AppResult<()> {
         std::process::exit(1);
     }
 
-    let config = ClickSendConfig::from_file(&config_path).expect("Failed to load config file");
+    let config = CliConfig::from_file(&config_path).expect("Failed to load config file");
 
-    // 3. Initialize the clicksend client
-    let client = ClickSendClient::new(
-        &config.api_key,
-        &config.username,
-        &config.base_url,
-        &config.version,
-    )?;
+    let command = match (args.command, args.send) {
+        (Some(command), _) => command,
+        (None, Some(send)) => Command::Send(send),
+        (None, None) => {
+            Cli::command().print_help().ok();
+            std::process::exit(2);
+        }
+    };
 
+    match command {
+        Command::Send(SendArgs {
+            sender,
+            recipient,
+            message,
+        }) => {
+            let provider = match config.sms_provider() {
+                Ok(provider) => provider,
+                Err(err) => {
+                    eprintln!("Error: {} in {:?}", err, config_path);
+                    std::process::exit(1);
+                }
+            };
+            send(provider.as_ref(), &sender, &recipient, &message).await
+        }
+        Command::Inbound { limit } => {
+            let Some(server) = config.server else {
+                eprintln!("Error: a [server] section is required in {:?}", config_path);
+                std::process::exit(1);
+            };
+            if let Err(err) = inbound(&server, limit).await {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn send(
+    provider: &dyn SmsProvider,
+    sender: &str,
+    recipient: &str,
+    message: &str,
+) -> AppResult<()> {
     let spinner = ProgressBar::new_spinner();
     spinner.set_message("Sending SMS...");
     spinner.enable_steady_tick(Duration::from_millis(100));
@@ -77,16 +209,59 @@ async fn main() -> AppResult<()> {
             .expect("Expect to be able to set a default template"),
     );
 
-    client
-        .send_single_sms(&args.recipient, &args.sender, &args.message)
-        .await?;
+    let sms = OutboundSms {
+        to: recipient.to_string(),
+        from: sender.to_string(),
+        body: message.to_string(),
+        custom_string: None,
+    };
+    let result = provider.send(&sms).await?;
 
     let success_message = format!(
-        "{}   SMS sent successfully!",
-        "\u{2713}".to_string().green()
+        "{}   SMS sent successfully! (message ID: {}, parts: {}, price: {})",
+        "\u{2713}".to_string().green(),
+        result.message_id.as_deref().unwrap_or("unknown"),
+        result.parts.unwrap_or(1),
+        result.price.as_deref().unwrap_or("unknown")
     );
 
     spinner.finish_with_message(success_message);
 
     Ok(())
 }
+
+async fn inbound(server: &ServerConfig, limit: u32) -> Result<(), reqwest::Error> {
+    let url = format!("{}/inbound", server.url.trim_end_matches('/'));
+
+    let response: InboundListResponse = reqwest::Client::new()
+        .get(&url)
+        .bearer_auth(&server.api_key)
+        .query(&[("limit", limit)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    if response.messages.is_empty() {
+        println!("No inbound messages");
+    }
+
+    for sms in response.messages {
+        println!(
+            "{}  {} -> {}{}",
+            sms.received_at
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+                .dimmed(),
+            sms.from.bold(),
+            sms.to,
+            sms.reply_to
+                .map(|id| format!(" (reply to {})", id))
+                .unwrap_or_default()
+        );
+        println!("    {}", sms.body);
+    }
+
+    Ok(())
+}
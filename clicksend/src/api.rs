@@ -1,4 +1,10 @@
-use crate::{clicksend::ClickSendApi, AppResult};
+use crate::{
+    clicksend::{
+        client::{OutboundSms, SmsMessageResult},
+        ClickSendApi,
+    },
+    AppResult,
+};
 
 pub struct MessageService<T: ClickSendApi> {
     client: T,
@@ -14,9 +20,16 @@ impl<T: ClickSendApi> MessageService<T> {
         recipient: &str,
         sender: &str,
         message: &str,
-    ) -> AppResult<()> {
+    ) -> AppResult<SmsMessageResult> {
         self.client
             .send_single_sms(recipient, sender, message)
             .await
     }
+
+    pub async fn send_bulk_sms(
+        &self,
+        messages: &[OutboundSms],
+    ) -> Vec<AppResult<SmsMessageResult>> {
+        self.client.send_bulk_sms(messages).await
+    }
 }
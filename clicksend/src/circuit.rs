@@ -0,0 +1,106 @@
+//! Stops sending to a provider that keeps failing, so callers fail fast (or
+//! fail over) instead of waiting on timeouts, and lets a trial request
+//! through now and then to notice when it recovers.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive transient failures that open the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a trial request is let through.
+    pub open_for: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 5,
+            open_for: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Healthy: requests go through.
+    Closed,
+    /// Failing: requests are refused until `open_for` has passed.
+    Open,
+    /// A single trial request is deciding whether to close the circuit again.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            inner: Mutex::new(Inner {
+                consecutive_failures: 0,
+                opened_at: None,
+                trial_in_flight: false,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let inner = self.inner.lock().unwrap();
+        match inner.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.config.open_for => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether a request may be made now. Once the circuit has been open for
+    /// long enough this lets exactly one trial request through; its outcome
+    /// must be reported with [`record_success`](Self::record_success) or
+    /// [`record_failure`](Self::record_failure).
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.opened_at {
+            None => true,
+            Some(opened_at) if opened_at.elapsed() < self.config.open_for => false,
+            Some(_) if inner.trial_in_flight => false,
+            Some(_) => {
+                inner.trial_in_flight = true;
+                true
+            }
+        }
+    }
+
+    /// The provider answered, so it's healthy (even if it refused the message).
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.trial_in_flight = false;
+    }
+
+    /// The provider couldn't be reached or failed transiently.
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        // A failed trial re-opens the circuit for another full period
+        if inner.trial_in_flight || inner.consecutive_failures >= self.config.failure_threshold {
+            inner.opened_at = Some(Instant::now());
+        }
+        inner.trial_in_flight = false;
+    }
+}
@@ -1,21 +1,109 @@
+use std::{sync::Arc, time::Duration};
+
 use base64::{engine::general_purpose, Engine};
+use rand::Rng;
 use reqwest::{
     header::{self, HeaderMap, HeaderValue},
-    Client,
+    Client, RequestBuilder, Response, StatusCode,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
+use tracing::warn;
 
-use super::ClickSendApi;
+use super::{
+    senders::{SenderCache, SenderList},
+    ClickSendApi,
+};
 use crate::{
+    circuit::{CircuitBreaker, CircuitBreakerConfig, CircuitState},
     error::{AppError, AppResult},
     validators::{self, validate_sender_logic},
 };
 use serde_json;
 
+/// How long fetched sender lists are trusted by default.
+pub const DEFAULT_SENDER_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Clones share the circuit breaker and the sender cache.
+#[derive(Clone)]
 pub struct ClickSendClient {
     client: Client,
+    /// Kept to rebuild `client` with new timeouts.
+    headers: HeaderMap,
     base_url: String,
     version: String,
+    retry_policy: RetryPolicy,
+    /// `None` when something else, like an [`SmsRouter`](crate::provider::SmsRouter),
+    /// already breaks the circuit.
+    breaker: Option<Arc<CircuitBreaker>>,
+    senders: Arc<SenderCache>,
+}
+
+/// How failed requests to ClickSend are retried. Lookups are retried after
+/// network errors, 429 and 5xx responses; sending, which isn't idempotent,
+/// only when ClickSend can't have acted on the request (connection failures
+/// and 429s).
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per call, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubling (with jitter) after that.
+    pub base_delay: Duration,
+    /// Longest delay between attempts. A `Retry-After` longer than this ends
+    /// the retries instead.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// How long calls to a provider may take, so one that hangs fails (and is
+/// retried, or trips the circuit breaker) instead of stalling its caller.
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    /// Connecting. A connection that times out never reached the provider.
+    pub connect: Duration,
+    /// The whole request, from connecting until the response has been read.
+    pub request: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Duration::from_secs(5),
+            request: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Timeouts {
+    /// A request client with these timeouts that sends `headers` with
+    /// every request.
+    pub(crate) fn client(&self, headers: HeaderMap) -> reqwest::Result<Client> {
+        Client::builder()
+            .default_headers(headers)
+            .connect_timeout(self.connect)
+            .timeout(self.request)
+            .build()
+    }
+}
+
+impl RetryPolicy {
+    /// Full jitter: a random delay up to the exponential backoff for `retry`
+    /// (counting from 1).
+    fn backoff(&self, retry: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry - 1))
+            .min(self.max_delay);
+        ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,6 +131,128 @@ struct DedicatedNumbersResponse {
     data: DedicatedNumbersData,
 }
 
+#[derive(Debug, Deserialize)]
+struct AlphaTag {
+    alpha_tag: String,
+    /// `approved`, `pending` or `rejected`.
+    status: String,
+}
+
+/// One page of `alpha-tags`.
+#[derive(Debug, Deserialize)]
+struct AlphaTagsData {
+    current_page: u32,
+    last_page: u32,
+    data: Vec<AlphaTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlphaTagsResponse {
+    data: AlphaTagsData,
+}
+
+/// Alpha tags fetched per `alpha-tags` page.
+const ALPHA_TAGS_PAGE_SIZE: u32 = 100;
+
+/// The most messages ClickSend accepts in one `sms/send` request.
+pub const MAX_MESSAGES_PER_REQUEST: usize = 1000;
+
+/// One message in a [`ClickSendApi::send_bulk_sms`] call.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboundSms {
+    pub to: String,
+    pub from: String,
+    pub body: String,
+    /// Free-form reference echoed back in delivery receipts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_string: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SmsSendRequest<'a> {
+    messages: Vec<SmsSendMessage<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SmsSendMessage<'a> {
+    #[serde(flatten)]
+    sms: &'a OutboundSms,
+    source: &'static str,
+}
+
+/// Body of a successful `sms/send` response.
+#[derive(Debug, Deserialize)]
+pub struct SmsSendResponse {
+    pub http_code: u16,
+    pub response_code: String,
+    pub response_msg: String,
+    pub data: SmsSendData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmsSendData {
+    #[serde(default)]
+    pub total_count: u32,
+    #[serde(default)]
+    pub queued_count: u32,
+    pub messages: Vec<SmsMessageResult>,
+}
+
+/// ClickSend's verdict on a single message.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmsMessageResult {
+    pub to: String,
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// `SUCCESS`, or the reason the message was refused (for example
+    /// `INVALID_RECIPIENT` or `INSUFFICIENT_CREDIT`).
+    pub status: String,
+    #[serde(default)]
+    pub message_parts: Option<u32>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub message_price: Option<String>,
+    #[serde(default)]
+    pub custom_string: Option<String>,
+}
+
+impl SmsMessageResult {
+    pub fn is_success(&self) -> bool {
+        self.status == "SUCCESS"
+    }
+
+    /// Turns a refused message into the matching error. ClickSend reports
+    /// these per message, inside an HTTP 200 response.
+    pub fn into_result(self) -> AppResult<Self> {
+        match self.status.as_str() {
+            "SUCCESS" => Ok(self),
+            "INVALID_RECIPIENT" => Err(AppError::InvalidPhoneNumber(self.to)),
+            "INVALID_SENDER_ID" => Err(AppError::InvalidSender(self.from.unwrap_or_default())),
+            "INSUFFICIENT_CREDIT" => Err(AppError::InsufficientCredit),
+            "COUNTRY_NOT_ENABLED" => Err(AppError::CountryNotEnabled(self.to)),
+            _ => Err(AppError::MessageRejected {
+                recipient: self.to,
+                status: self.status,
+            }),
+        }
+    }
+}
+
+/// ClickSend sends prices as strings, but tolerate plain numbers too.
+fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(value)) => Some(value),
+            Some(serde_json::Value::Number(value)) => Some(value.to_string()),
+            _ => None,
+        },
+    )
+}
+
 impl ClickSendClient {
     pub fn new(api_key: &str, username: &str, base_url: &str, version: &str) -> AppResult<Self> {
         // Construct basic auth credentials and encode them
@@ -66,21 +276,197 @@ impl ClickSendClient {
             })?,
         );
 
-        // Build the reqwest client with the default headers
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(|_| {
-                AppError::ClickSendApiError("Unable to construct request client".into())
-            })?;
-
         Ok(Self {
-            client,
+            client: client(&Timeouts::default(), &headers)?,
+            headers,
             base_url: base_url.to_string(),
             version: version.to_string(),
+            retry_policy: RetryPolicy::default(),
+            breaker: Some(Arc::new(CircuitBreaker::new(
+                CircuitBreakerConfig::default(),
+            ))),
+            senders: Arc::new(SenderCache::new(DEFAULT_SENDER_CACHE_TTL)),
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> AppResult<Self> {
+        self.client = client(&timeouts, &self.headers)?;
+        Ok(self)
+    }
+
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.breaker = Some(Arc::new(CircuitBreaker::new(config)));
+        self
+    }
+
+    /// Leaves circuit breaking to the caller, e.g. when the client is one of
+    /// an [`SmsRouter`](crate::provider::SmsRouter)'s routes, which have
+    /// breakers of their own.
+    pub fn without_circuit_breaker(mut self) -> Self {
+        self.breaker = None;
+        self
+    }
+
+    /// How long sender lists are cached for; zero fetches them for every
+    /// validation.
+    pub fn with_sender_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.senders = Arc::new(SenderCache::new(ttl));
+        self
+    }
+
+    /// Drops the cached sender lists, e.g. after numbers were added to or
+    /// removed from the account.
+    pub fn invalidate_senders(&self) {
+        self.senders.invalidate();
+    }
+
+    /// Fetches every sender list into the cache.
+    pub async fn refresh_senders(&self) -> AppResult<()> {
+        self.senders.insert(
+            SenderList::VerifiedNumbers,
+            self.fetch_verified_numbers().await?,
+        );
+        self.senders.insert(
+            SenderList::DedicatedNumbers,
+            self.fetch_dedicated_numbers().await?,
+        );
+        self.senders
+            .insert(SenderList::AlphaTags, self.fetch_alpha_tags().await?);
+        Ok(())
+    }
+
+    /// Refreshes the sender lists every `interval` in the background, so
+    /// sends don't wait on fetching them. Set the interval below the cache
+    /// TTL; a failed refresh is logged and leaves the lists to expire as
+    /// usual.
+    pub fn spawn_sender_refresh(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let client = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = client.refresh_senders().await {
+                    warn!("Failed to refresh ClickSend senders: {}", err);
+                }
+                tokio::time::sleep(interval).await;
+            }
         })
     }
 
+    /// A sender list, from the cache while it's fresh.
+    async fn sender_list(&self, list: SenderList) -> AppResult<Vec<String>> {
+        if let Some(values) = self.senders.get(list) {
+            return Ok(values);
+        }
+
+        let values = match list {
+            SenderList::VerifiedNumbers => self.fetch_verified_numbers().await?,
+            SenderList::DedicatedNumbers => self.fetch_dedicated_numbers().await?,
+            SenderList::AlphaTags => self.fetch_alpha_tags().await?,
+        };
+        self.senders.insert(list, values.clone());
+        Ok(values)
+    }
+
+    /// Whether calls are currently going through to ClickSend. Always
+    /// closed without a circuit breaker.
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker
+            .as_ref()
+            .map_or(CircuitState::Closed, |breaker| breaker.state())
+    }
+
+    /// Makes the request built by `request`, retrying under the retry policy,
+    /// and returns the first successful response. Calls are refused while
+    /// the circuit breaker is open.
+    async fn execute<F>(&self, request: F, idempotent: bool) -> AppResult<Response>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        if self
+            .breaker
+            .as_ref()
+            .is_some_and(|breaker| !breaker.allow())
+        {
+            return Err(AppError::CircuitOpen);
+        }
+
+        let mut attempt = 1;
+        let result = loop {
+            let (err, retry_after) = match request().send().await {
+                Ok(res) if res.status().is_success() => break Ok(res),
+                Ok(res) => {
+                    let status = res.status();
+                    let retryable = status == StatusCode::TOO_MANY_REQUESTS
+                        || (idempotent && status.is_server_error());
+                    if !retryable {
+                        break Err(error_from_response(res).await);
+                    }
+                    let retry_after = retry_after(&res);
+                    (error_from_response(res).await, retry_after)
+                }
+                Err(err) if idempotent || err.is_connect() => (AppError::from_request(err), None),
+                Err(err) => break Err(AppError::from_request(err)),
+            };
+
+            if attempt >= self.retry_policy.max_attempts {
+                break Err(err);
+            }
+            let delay = match retry_after {
+                Some(delay) if delay > self.retry_policy.max_delay => break Err(err),
+                Some(delay) => delay,
+                None => self.retry_policy.backoff(attempt),
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+
+        if let Some(breaker) = &self.breaker {
+            match &result {
+                Err(err) if err.is_transient() => breaker.record_failure(),
+                // ClickSend answered, even if it refused the request
+                _ => breaker.record_success(),
+            }
+        }
+        result
+    }
+
+    /// Sends one `sms/send` request for up to [`MAX_MESSAGES_PER_REQUEST`]
+    /// messages and returns ClickSend's per-message results, in order.
+    async fn post_sms(&self, messages: &[&OutboundSms]) -> AppResult<Vec<SmsMessageResult>> {
+        let url = self.construct_url("sms/send");
+        let payload = SmsSendRequest {
+            messages: messages
+                .iter()
+                .map(|sms| SmsSendMessage { sms, source: "api" })
+                .collect(),
+        };
+
+        let body = self
+            .execute(|| self.client.post(&url).json(&payload), false)
+            .await?
+            .text()
+            .await
+            .map_err(|err| AppError::MessageSendFailed(err.to_string()))?;
+
+        let parsed: SmsSendResponse = serde_json::from_str(&body).map_err(|err| {
+            AppError::ClickSendApiError(format!("Unexpected sms/send response: {}", err))
+        })?;
+
+        if parsed.data.messages.len() != messages.len() {
+            return Err(AppError::ClickSendApiError(format!(
+                "Sent {} messages but got {} results",
+                messages.len(),
+                parsed.data.messages.len()
+            )));
+        }
+
+        Ok(parsed.data.messages)
+    }
+
     fn construct_url(&self, endpoint: &str) -> String {
         let url = format!("{}/{}/{}", self.base_url, self.version, endpoint);
 
@@ -88,146 +474,226 @@ impl ClickSendClient {
     }
 }
 
+/// The delay a 429 or 503 response asks for, when given in seconds.
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Builds the reqwest client with the default headers.
+fn client(timeouts: &Timeouts, headers: &HeaderMap) -> AppResult<Client> {
+    timeouts
+        .client(headers.clone())
+        .map_err(|_| AppError::ClickSendApiError("Unable to construct request client".into()))
+}
+
+/// Turns a non-success response into an error carrying its status and body.
+async fn error_from_response(res: Response) -> AppError {
+    let status = res.status().as_u16();
+    let body = res
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    AppError::HttpStatus { status, body }
+}
+
 #[async_trait::async_trait]
 impl ClickSendApi for ClickSendClient {
     async fn validate_sender(&self, sender: &str) -> AppResult<()> {
-        validate_sender_logic(
-            sender,
-            validators::validate_e164,
-            || Box::pin(self.fetch_verified_numbers()),
-            || Box::pin(self.fetch_dedicated_numbers()),
-            || Box::pin(self.fetch_alpha_tags()),
-        )
-        .await
+        let validate = || {
+            validate_sender_logic(
+                sender,
+                validators::validate_e164,
+                || Box::pin(self.sender_list(SenderList::VerifiedNumbers)),
+                || Box::pin(self.sender_list(SenderList::DedicatedNumbers)),
+                || Box::pin(self.sender_list(SenderList::AlphaTags)),
+            )
+        };
+
+        let cached = !self.senders.is_empty();
+        match validate().await {
+            // The sender may have been added since the lists were cached
+            Err(AppError::InvalidSender(_)) if cached => {
+                self.invalidate_senders();
+                validate().await
+            }
+            result => result,
+        }
     }
 
-    async fn send_single_sms(&self, recipient: &str, sender: &str, message: &str) -> AppResult<()> {
+    async fn send_single_sms(
+        &self,
+        recipient: &str,
+        sender: &str,
+        message: &str,
+    ) -> AppResult<SmsMessageResult> {
         // 1. Validate recipient number (must be in E.164 format)
         validators::validate_e164(recipient)?;
 
         // 2. Validate the sender (either own number, dedicated number or alpha tag)
         self.validate_sender(sender).await?;
 
-        // 3. build the request URL for sending SMS
-        let url = self.construct_url("sms/send");
+        // 3. Send it, and check ClickSend accepted this particular message
+        let sms = OutboundSms {
+            to: recipient.to_string(),
+            from: sender.to_string(),
+            body: message.to_string(),
+            custom_string: None,
+        };
+        let mut results = self.post_sms(&[&sms]).await?;
+
+        let result = results.remove(0).into_result();
+        if let Err(AppError::InvalidSender(_)) = result {
+            // The cached lists let through a sender ClickSend no longer accepts
+            self.invalidate_senders();
+        }
+        result
+    }
+
+    async fn send_bulk_sms(&self, messages: &[OutboundSms]) -> Vec<AppResult<SmsMessageResult>> {
+        let mut results: Vec<Option<AppResult<SmsMessageResult>>> = vec![None; messages.len()];
 
-        // 4. Prepare the payload for the SMS request
-        let payload = serde_json::json!({
-            "messages": [
-                {
-                    "body": message,
-                    "to": recipient,
-                    "from": sender,
-                    "source": "api",
+        // 1. Validate each distinct sender once
+        let mut senders: Vec<&str> = messages.iter().map(|sms| sms.from.as_str()).collect();
+        senders.sort_unstable();
+        senders.dedup();
+        let mut invalid_senders = Vec::new();
+        for sender in senders {
+            if let Err(err) = self.validate_sender(sender).await {
+                invalid_senders.push((sender, err));
+            }
+        }
+
+        // 2. Reject bad senders and recipients locally; everything else gets sent
+        let mut sendable = Vec::new();
+        for (index, sms) in messages.iter().enumerate() {
+            if let Some((_, err)) = invalid_senders
+                .iter()
+                .find(|(sender, _)| *sender == sms.from)
+            {
+                results[index] = Some(Err(err.clone()));
+            } else if let Err(err) = validators::validate_e164(&sms.to) {
+                results[index] = Some(Err(err));
+            } else {
+                sendable.push(index);
+            }
+        }
+
+        // 3. Send in batches ClickSend will accept
+        for chunk in sendable.chunks(MAX_MESSAGES_PER_REQUEST) {
+            let batch: Vec<&OutboundSms> = chunk.iter().map(|&index| &messages[index]).collect();
+            match self.post_sms(&batch).await {
+                Ok(sent) => {
+                    for (&index, result) in chunk.iter().zip(sent) {
+                        let result = result.into_result();
+                        if let Err(AppError::InvalidSender(_)) = result {
+                            self.invalidate_senders();
+                        }
+                        results[index] = Some(result);
+                    }
+                }
+                Err(err) => {
+                    for &index in chunk {
+                        results[index] = Some(Err(err.clone()));
+                    }
                 }
-            ]
-        });
-
-        // 5. Make the API request
-        let response = self.client.post(&url).json(&payload).send().await;
-
-        // 6. Handle the API response
-        match response {
-            Ok(res) if res.status().is_success() => Ok(()), // SMS sent successfully
-            Ok(res) => {
-                let status = res.status();
-                let error_message = res
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                Err(AppError::MessageSendFailed(format!(
-                    "Failed with status {}: {}",
-                    status, error_message
-                )))
             }
-            Err(err) => Err(AppError::MessageSendFailed(err.to_string())),
         }
+
+        results.into_iter().flatten().collect()
     }
 
     async fn fetch_verified_numbers(&self) -> AppResult<Vec<String>> {
         let url = self.construct_url("own-numbers");
-        let response = self.client.get(&url).send().await;
-
-        match response {
-            Ok(res) if res.status().is_success() => {
-                let body_text = res
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Failed to get response text".to_string());
-
-                let own_numbers_response: OwnNumbersResponse = serde_json::from_str(&body_text)
-                    .unwrap_or_else(|err| {
-                        println!("Failed to deserialize JSON: {}", err);
-                        OwnNumbersResponse {
-                            own_numbers: Vec::new(),
-                        }
-                    });
+        let res = self.execute(|| self.client.get(&url), true).await?;
 
-                let phone_numbers: Vec<String> = own_numbers_response
-                    .own_numbers
-                    .into_iter()
-                    .map(|own_number| own_number.phone_number)
-                    .collect();
-                Ok(phone_numbers)
-            }
-            Ok(res) => {
-                let status = res.status();
-                let error_message = res
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                Err(AppError::MessageSendFailed(format!(
-                    "Failed with status {}: {}",
-                    status, error_message
-                )))
-            }
-            Err(err) => Err(AppError::MessageSendFailed(err.to_string())),
-        }
+        let body_text = res
+            .text()
+            .await
+            .unwrap_or_else(|_| "Failed to get response text".to_string());
+
+        let own_numbers_response: OwnNumbersResponse = serde_json::from_str(&body_text)
+            .unwrap_or_else(|err| {
+                println!("Failed to deserialize JSON: {}", err);
+                OwnNumbersResponse {
+                    own_numbers: Vec::new(),
+                }
+            });
+
+        let phone_numbers: Vec<String> = own_numbers_response
+            .own_numbers
+            .into_iter()
+            .map(|own_number| own_number.phone_number)
+            .collect();
+        Ok(phone_numbers)
     }
 
     async fn fetch_dedicated_numbers(&self) -> AppResult<Vec<String>> {
         let url = self.construct_url("numbers");
-        let response = self.client.get(&url).send().await;
-
-        match response {
-            Ok(res) if res.status().is_success() => {
-                let body_text = res
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Failed to get response text".to_string());
-
-                let dedicated_numbers: DedicatedNumbersResponse = serde_json::from_str(&body_text)
-                    .unwrap_or_else(|err| {
-                        println!("Failed to deserialize JSON: {}", err);
-                        DedicatedNumbersResponse {
-                            data: DedicatedNumbersData { data: Vec::new() },
-                        }
-                    });
+        let res = self.execute(|| self.client.get(&url), true).await?;
+
+        let body_text = res
+            .text()
+            .await
+            .unwrap_or_else(|_| "Failed to get response text".to_string());
 
-                let phone_numbers: Vec<String> = dedicated_numbers
+        let dedicated_numbers: DedicatedNumbersResponse = serde_json::from_str(&body_text)
+            .unwrap_or_else(|err| {
+                println!("Failed to deserialize JSON: {}", err);
+                DedicatedNumbersResponse {
+                    data: DedicatedNumbersData { data: Vec::new() },
+                }
+            });
+
+        let phone_numbers: Vec<String> = dedicated_numbers
+            .data
+            .data
+            .into_iter()
+            .map(|dedicated_number| dedicated_number.dedicated_number)
+            .collect();
+        Ok(phone_numbers)
+    }
+
+    /// Approved alpha tags only; pending and rejected ones can't be sent from.
+    async fn fetch_alpha_tags(&self) -> AppResult<Vec<String>> {
+        let url = self.construct_url("alpha-tags");
+        let mut alpha_tags = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let query = [("page", page), ("limit", ALPHA_TAGS_PAGE_SIZE)];
+            let res = self
+                .execute(|| self.client.get(&url).query(&query), true)
+                .await?;
+            let body_text = res
+                .text()
+                .await
+                .map_err(|err| AppError::MessageSendFailed(err.to_string()))?;
+
+            // Unlike the number lookups, an unreadable list is an error rather
+            // than empty: it would otherwise reject every alpha sender.
+            let response: AlphaTagsResponse = serde_json::from_str(&body_text).map_err(|err| {
+                AppError::ClickSendApiError(format!("Unexpected alpha-tags response: {}", err))
+            })?;
+
+            alpha_tags.extend(
+                response
                     .data
                     .data
                     .into_iter()
-                    .map(|dedicated_number| dedicated_number.dedicated_number)
-                    .collect();
-                Ok(phone_numbers)
-            }
-            Ok(res) => {
-                let status = res.status();
-                let error_message = res
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                Err(AppError::MessageSendFailed(format!(
-                    "Failed to fetch verified numbers {}: {}",
-                    status, error_message
-                )))
+                    .filter(|tag| tag.status.eq_ignore_ascii_case("approved"))
+                    .map(|tag| tag.alpha_tag),
+            );
+
+            if response.data.current_page >= response.data.last_page {
+                return Ok(alpha_tags);
             }
-            Err(err) => Err(AppError::MessageSendFailed(err.to_string())),
+            page = response.data.current_page + 1;
         }
     }
-
-    async fn fetch_alpha_tags(&self) -> AppResult<Vec<String>> {
-        Ok([].to_vec())
-    }
 }
@@ -3,7 +3,10 @@ use crate::{
     validators::{self, validate_sender_logic},
 };
 
-use super::ClickSendApi;
+use super::{
+    client::{OutboundSms, SmsMessageResult},
+    ClickSendApi,
+};
 
 pub struct MockClickSendClient;
 
@@ -19,7 +22,12 @@ impl ClickSendApi for MockClickSendClient {
         )
         .await
     }
-    async fn send_single_sms(&self, recipient: &str, sender: &str, message: &str) -> AppResult<()> {
+    async fn send_single_sms(
+        &self,
+        recipient: &str,
+        sender: &str,
+        message: &str,
+    ) -> AppResult<SmsMessageResult> {
         // Validate recipient number
         validators::validate_e164(recipient)?;
         self.validate_sender(sender).await?;
@@ -28,7 +36,44 @@ impl ClickSendApi for MockClickSendClient {
             "Sending message from '{}' to '{}' - {}",
             recipient, sender, message
         );
-        Ok(())
+        Ok(SmsMessageResult {
+            to: recipient.to_string(),
+            from: Some(sender.to_string()),
+            message_id: Some("MOCK-0".to_string()),
+            status: "SUCCESS".to_string(),
+            message_parts: Some(1),
+            message_price: Some("0.0000".to_string()),
+            custom_string: None,
+        })
+    }
+
+    async fn send_bulk_sms(&self, messages: &[OutboundSms]) -> Vec<AppResult<SmsMessageResult>> {
+        let mut results = Vec::with_capacity(messages.len());
+
+        for (index, sms) in messages.iter().enumerate() {
+            let result = match validators::validate_e164(&sms.to) {
+                Ok(()) => self.validate_sender(&sms.from).await,
+                Err(err) => Err(err),
+            };
+
+            results.push(result.map(|()| {
+                println!(
+                    "Sending message from '{}' to '{}' - {}",
+                    sms.from, sms.to, sms.body
+                );
+                SmsMessageResult {
+                    to: sms.to.clone(),
+                    from: Some(sms.from.clone()),
+                    message_id: Some(format!("MOCK-{}", index)),
+                    status: "SUCCESS".to_string(),
+                    message_parts: Some(1),
+                    message_price: Some("0.0000".to_string()),
+                    custom_string: sms.custom_string.clone(),
+                }
+            }));
+        }
+
+        results
     }
 
     async fn fetch_verified_numbers(&self) -> AppResult<Vec<String>> {
@@ -40,6 +85,6 @@ impl ClickSendApi for MockClickSendClient {
     }
 
     async fn fetch_alpha_tags(&self) -> AppResult<Vec<String>> {
-        Ok(vec!["MYBUSINESS".to_string(), "ALPHAEXAMPLE".to_string()])
+        Ok(vec!["MYBUSINESS".to_string(), "EXAMPLE".to_string()])
     }
 }
@@ -1,12 +1,23 @@
 pub mod client;
 pub mod mock;
+pub mod senders;
 use crate::error::AppResult;
+use client::{OutboundSms, SmsMessageResult};
 
 #[async_trait::async_trait]
 pub trait ClickSendApi {
     async fn fetch_verified_numbers(&self) -> AppResult<Vec<String>>;
     async fn fetch_dedicated_numbers(&self) -> AppResult<Vec<String>>;
     async fn fetch_alpha_tags(&self) -> AppResult<Vec<String>>;
-    async fn send_single_sms(&self, recipient: &str, sender: &str, message: &str) -> AppResult<()>;
+    async fn send_single_sms(
+        &self,
+        recipient: &str,
+        sender: &str,
+        message: &str,
+    ) -> AppResult<SmsMessageResult>;
+    /// Sends many messages, batching them into as few requests as ClickSend
+    /// allows. Returns one result per message, in the order given; messages
+    /// ClickSend refused are errors, as with `send_single_sms`.
+    async fn send_bulk_sms(&self, messages: &[OutboundSms]) -> Vec<AppResult<SmsMessageResult>>;
     async fn validate_sender(&self, sender: &str) -> AppResult<()>;
 }
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The lists a sender is checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SenderList {
+    VerifiedNumbers,
+    DedicatedNumbers,
+    AlphaTags,
+}
+
+/// Sender lists fetched from ClickSend, kept for `ttl` so validating a sender
+/// doesn't cost extra requests on every send.
+#[derive(Debug)]
+pub struct SenderCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<SenderList, (Instant, Vec<String>)>>,
+}
+
+impl SenderCache {
+    /// A zero `ttl` disables caching.
+    pub fn new(ttl: Duration) -> Self {
+        SenderCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached list, unless it is missing or older than the TTL.
+    pub fn get(&self, list: SenderList) -> Option<Vec<String>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&list)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, values)| values.clone())
+    }
+
+    /// Whether no list is cached, or every cached list is out of date.
+    pub fn is_empty(&self) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .values()
+            .all(|(fetched_at, _)| fetched_at.elapsed() >= self.ttl)
+    }
+
+    pub fn insert(&self, list: SenderList, values: Vec<String>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(list, (Instant::now(), values));
+    }
+
+    /// Forgets every list, so the next validation fetches them again.
+    pub fn invalidate(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
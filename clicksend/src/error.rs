@@ -3,21 +3,113 @@ use std::fmt;
 
 pub type AppResult<T> = Result<T, AppError>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AppError {
     InvalidSender(String),
     InvalidPhoneNumber(String),
+    InvalidAlphaTag(String),
     MessageSendFailed(String),
+    /// The provider couldn't be reached, so it never saw the request.
+    ConnectionFailed(String),
     ClickSendApiError(String),
+    TwilioApiError(String),
+    HttpStatus {
+        status: u16,
+        body: String,
+    },
+    InsufficientCredit,
+    CountryNotEnabled(String),
+    MessageRejected {
+        recipient: String,
+        status: String,
+    },
+    TooManySegments {
+        segments: usize,
+        max: usize,
+    },
+    NoRoute(String),
+    CircuitOpen,
+    ProvidersUnavailable(String),
+}
+
+impl AppError {
+    /// Whether the failure is worth retrying: network errors, rate limiting
+    /// and server-side (5xx) errors from the provider.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AppError::MessageSendFailed(_)
+            | AppError::ConnectionFailed(_)
+            | AppError::ProvidersUnavailable(_)
+            | AppError::CircuitOpen => true,
+            AppError::HttpStatus { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Whether the provider can't have acted on the request, so sending the
+    /// message elsewhere won't deliver it twice. Other transient failures,
+    /// like a dropped response or a 5xx, may follow an accepted message.
+    pub fn is_unsent(&self) -> bool {
+        match self {
+            AppError::ConnectionFailed(_)
+            | AppError::ProvidersUnavailable(_)
+            | AppError::CircuitOpen => true,
+            AppError::HttpStatus { status, .. } => *status == 429,
+            _ => false,
+        }
+    }
+
+    /// Maps a failed request, telling connection failures apart.
+    pub(crate) fn from_request(err: reqwest::Error) -> Self {
+        if err.is_connect() {
+            AppError::ConnectionFailed(err.to_string())
+        } else {
+            AppError::MessageSendFailed(err.to_string())
+        }
+    }
 }
 
 impl fmt::Display for AppError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AppError::InvalidPhoneNumber(number) => write!(f, "Invalid Phone number: {}", number),
+            AppError::InvalidAlphaTag(tag) => write!(
+                f,
+                "Alpha tag must be 1 to 11 letters, digits, spaces, '-' or '.', including a letter: {}",
+                tag
+            ),
             AppError::InvalidSender(sender) => write!(f, "Sender ID must be either a registered alpha tag, a verified own number, or a purchased dedicated number: {}", sender),
             AppError::MessageSendFailed(err) => write!(f, "Failed to send message: {}", err),
-            AppError::ClickSendApiError(err) => write!(f, "ClickSend API Error: {}", err)
+            AppError::ConnectionFailed(err) => {
+                write!(f, "Failed to connect to the SMS provider: {}", err)
+            }
+            AppError::ClickSendApiError(err) => write!(f, "ClickSend API Error: {}", err),
+            AppError::TwilioApiError(err) => write!(f, "Twilio API Error: {}", err),
+            AppError::HttpStatus { status, body } => {
+                write!(f, "SMS provider request failed with status {}: {}", status, body)
+            }
+            AppError::InsufficientCredit => write!(f, "Insufficient ClickSend credit"),
+            AppError::CountryNotEnabled(recipient) => {
+                write!(f, "Sending to this country is not enabled: {}", recipient)
+            }
+            AppError::MessageRejected { recipient, status } => {
+                write!(f, "ClickSend rejected message to {}: {}", recipient, status)
+            }
+            AppError::TooManySegments { segments, max } => write!(
+                f,
+                "Message needs {} SMS segments, more than the maximum of {}",
+                segments, max
+            ),
+            AppError::NoRoute(recipient) => write!(f, "No SMS provider serves {}", recipient),
+            AppError::CircuitOpen => write!(
+                f,
+                "ClickSend requests are paused after repeated failures; try again later"
+            ),
+            AppError::ProvidersUnavailable(recipient) => write!(
+                f,
+                "Every SMS provider serving {} is failing; try again later",
+                recipient
+            ),
         }
     }
 }
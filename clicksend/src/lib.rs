@@ -1,7 +1,10 @@
 pub mod api;
+pub mod circuit;
 pub mod clicksend;
 pub mod error;
+pub mod provider;
 pub mod validators;
 
 pub use clicksend::client::ClickSendClient;
 pub use error::{AppError, AppResult};
+pub use provider::SmsProvider;
@@ -0,0 +1,77 @@
+use super::{Capabilities, SentSms, SmsProvider};
+use crate::{
+    clicksend::{
+        client::{OutboundSms, SmsMessageResult, MAX_MESSAGES_PER_REQUEST},
+        ClickSendApi,
+    },
+    error::AppResult,
+    ClickSendClient,
+};
+
+/// [`SmsProvider`] backed by a ClickSend client.
+pub struct ClickSendProvider<T: ClickSendApi = ClickSendClient> {
+    client: T,
+}
+
+impl<T: ClickSendApi> ClickSendProvider<T> {
+    pub fn new(client: T) -> Self {
+        ClickSendProvider { client }
+    }
+
+    pub fn client(&self) -> &T {
+        &self.client
+    }
+}
+
+impl From<SmsMessageResult> for SentSms {
+    fn from(result: SmsMessageResult) -> Self {
+        SentSms {
+            to: result.to,
+            message_id: result.message_id,
+            parts: result.message_parts,
+            price: result.message_price,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: ClickSendApi + Send + Sync> SmsProvider for ClickSendProvider<T> {
+    fn name(&self) -> &'static str {
+        "clicksend"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            bulk_send: true,
+            max_batch_size: MAX_MESSAGES_PER_REQUEST,
+            alpha_senders: true,
+            delivery_receipts: true,
+            custom_references: true,
+        }
+    }
+
+    async fn validate_sender(&self, sender: &str) -> AppResult<()> {
+        self.client.validate_sender(sender).await
+    }
+
+    async fn send(&self, sms: &OutboundSms) -> AppResult<SentSms> {
+        if sms.custom_string.is_some() {
+            let mut results = self.client.send_bulk_sms(std::slice::from_ref(sms)).await;
+            return results.remove(0).map(SentSms::from);
+        }
+
+        self.client
+            .send_single_sms(&sms.to, &sms.from, &sms.body)
+            .await
+            .map(SentSms::from)
+    }
+
+    async fn send_many(&self, messages: &[OutboundSms]) -> Vec<AppResult<SentSms>> {
+        self.client
+            .send_bulk_sms(messages)
+            .await
+            .into_iter()
+            .map(|result| result.map(SentSms::from))
+            .collect()
+    }
+}
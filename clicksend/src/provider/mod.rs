@@ -0,0 +1,62 @@
+//! Vendor-neutral sending. Callers that only need to send messages should
+//! depend on [`SmsProvider`] rather than a particular vendor's client.
+
+pub mod clicksend;
+pub mod routing;
+pub mod twilio;
+
+use crate::{clicksend::client::OutboundSms, error::AppResult};
+
+pub use self::clicksend::ClickSendProvider;
+pub use routing::{RouteOptions, SmsRouter};
+pub use twilio::TwilioClient;
+
+/// What a provider supports, so callers can adapt rather than fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Many messages can go out in a single request.
+    pub bulk_send: bool,
+    /// Most messages [`SmsProvider::send_many`] sends per request.
+    pub max_batch_size: usize,
+    /// Alphanumeric sender IDs (like `MYBUSINESS`) can be used.
+    pub alpha_senders: bool,
+    /// Delivery receipts can be delivered to a webhook.
+    pub delivery_receipts: bool,
+    /// A message's `custom_string` is echoed back in its delivery receipt.
+    pub custom_references: bool,
+}
+
+/// A message the provider accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentSms {
+    pub to: String,
+    /// The provider's ID for the message, used to match delivery receipts.
+    pub message_id: Option<String>,
+    /// How many SMS parts the message was billed as.
+    pub parts: Option<u32>,
+    pub price: Option<String>,
+}
+
+#[async_trait::async_trait]
+pub trait SmsProvider: Send + Sync {
+    /// Short lowercase name, as used in configuration.
+    fn name(&self) -> &'static str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Checks the sender is one the account may send from.
+    async fn validate_sender(&self, sender: &str) -> AppResult<()>;
+
+    /// Sends one message. Messages the provider refuses are errors.
+    async fn send(&self, sms: &OutboundSms) -> AppResult<SentSms>;
+
+    /// Sends many messages, returning one result per message in the order
+    /// given. Providers without bulk sending send them one at a time.
+    async fn send_many(&self, messages: &[OutboundSms]) -> Vec<AppResult<SentSms>> {
+        let mut results = Vec::with_capacity(messages.len());
+        for sms in messages {
+            results.push(self.send(sms).await);
+        }
+        results
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{Capabilities, SentSms, SmsProvider};
+use crate::{
+    circuit::{CircuitBreaker, CircuitBreakerConfig, CircuitState},
+    clicksend::client::OutboundSms,
+    error::{AppError, AppResult},
+};
+
+/// How one provider takes part in routing.
+#[derive(Debug, Clone)]
+pub struct RouteOptions {
+    /// Identifies the route in health reports, e.g. `clicksend-au`.
+    pub name: String,
+    /// Share of traffic relative to the other routes serving a destination.
+    pub weight: u32,
+    /// Destination prefixes (like `+61`) this route serves. A route without
+    /// prefixes serves destinations no other route claims.
+    pub prefixes: Vec<String>,
+    /// Sender to use instead of the message's, for accounts that send from
+    /// their own numbers.
+    pub sender: Option<String>,
+}
+
+struct Route {
+    provider: Box<dyn SmsProvider>,
+    options: RouteOptions,
+    breaker: CircuitBreaker,
+}
+
+/// [`SmsProvider`] that spreads messages over several providers by
+/// destination and weight, failing over to the next provider when one can't
+/// be reached. Each provider has a circuit breaker, so one that keeps
+/// failing is skipped until it recovers.
+pub struct SmsRouter {
+    routes: Vec<Route>,
+    breaker_config: CircuitBreakerConfig,
+    /// Drives the weighted round robin.
+    counter: AtomicU64,
+}
+
+impl SmsRouter {
+    pub fn new(breaker_config: CircuitBreakerConfig) -> Self {
+        SmsRouter {
+            routes: Vec::new(),
+            breaker_config,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_route(mut self, provider: Box<dyn SmsProvider>, options: RouteOptions) -> Self {
+        self.routes.push(Route {
+            provider,
+            options,
+            breaker: CircuitBreaker::new(self.breaker_config.clone()),
+        });
+        self
+    }
+
+    /// Each route's name and circuit state, in the order they were added.
+    pub fn health(&self) -> Vec<(&str, CircuitState)> {
+        self.routes
+            .iter()
+            .map(|route| (route.options.name.as_str(), route.breaker.state()))
+            .collect()
+    }
+
+    /// Routes serving `to`, in the order they should be tried: the routes
+    /// with the longest matching prefix, starting from a weighted pick, then
+    /// the rest by descending weight.
+    fn candidates(&self, to: &str) -> Vec<&Route> {
+        let longest_match = |route: &Route| {
+            route
+                .options
+                .prefixes
+                .iter()
+                .filter(|prefix| to.starts_with(prefix.as_str()))
+                .map(|prefix| prefix.len())
+                .max()
+        };
+        let best = self.routes.iter().filter_map(longest_match).max();
+        let mut candidates: Vec<&Route> = self
+            .routes
+            .iter()
+            .filter(|route| match best {
+                Some(best) => longest_match(route) == Some(best),
+                None => route.options.prefixes.is_empty(),
+            })
+            .collect();
+        candidates.sort_by_key(|route| std::cmp::Reverse(route.options.weight));
+
+        let total: u64 = candidates
+            .iter()
+            .map(|route| route.options.weight as u64)
+            .sum();
+        if total > 0 {
+            let mut pick = self.counter.fetch_add(1, Ordering::Relaxed) % total;
+            let first = candidates
+                .iter()
+                .position(|route| {
+                    let weight = route.options.weight as u64;
+                    if pick < weight {
+                        true
+                    } else {
+                        pick -= weight;
+                        false
+                    }
+                })
+                .unwrap_or(0);
+            let primary = candidates.remove(first);
+            candidates.insert(0, primary);
+        }
+        candidates
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsProvider for SmsRouter {
+    fn name(&self) -> &'static str {
+        "router"
+    }
+
+    /// Only what every route supports, since any of them may send a message.
+    fn capabilities(&self) -> Capabilities {
+        let all = |supports: fn(&Capabilities) -> bool| {
+            self.routes
+                .iter()
+                .all(|route| supports(&route.provider.capabilities()))
+        };
+        Capabilities {
+            bulk_send: false,
+            max_batch_size: 1,
+            alpha_senders: all(|capabilities| capabilities.alpha_senders),
+            delivery_receipts: all(|capabilities| capabilities.delivery_receipts),
+            custom_references: all(|capabilities| capabilities.custom_references),
+        }
+    }
+
+    /// Checks the sender with every route that would send from it.
+    async fn validate_sender(&self, sender: &str) -> AppResult<()> {
+        for route in self
+            .routes
+            .iter()
+            .filter(|route| route.options.sender.is_none())
+        {
+            route.provider.validate_sender(sender).await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&self, sms: &OutboundSms) -> AppResult<SentSms> {
+        let candidates = self.candidates(&sms.to);
+        if candidates.is_empty() {
+            return Err(AppError::NoRoute(sms.to.clone()));
+        }
+
+        let mut last_error = None;
+        for route in candidates {
+            if !route.breaker.allow() {
+                continue;
+            }
+
+            let result = match &route.options.sender {
+                Some(sender) => {
+                    let sms = OutboundSms {
+                        from: sender.clone(),
+                        ..sms.clone()
+                    };
+                    route.provider.send(&sms).await
+                }
+                None => route.provider.send(sms).await,
+            };
+            match result {
+                Err(err) if err.is_transient() => {
+                    route.breaker.record_failure();
+                    // The provider may have sent it anyway; another would send it twice
+                    if !err.is_unsent() {
+                        return Err(err);
+                    }
+                    last_error = Some(err);
+                }
+                // The provider answered, even if it refused this message
+                result => {
+                    route.breaker.record_success();
+                    return result;
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| AppError::ProvidersUnavailable(sms.to.clone())))
+    }
+}
@@ -0,0 +1,241 @@
+use reqwest::{header, Client, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{Capabilities, SentSms, SmsProvider};
+use crate::{
+    clicksend::client::{OutboundSms, Timeouts},
+    error::{AppError, AppResult},
+    validators,
+};
+
+/// Twilio's production API.
+pub const DEFAULT_BASE_URL: &str = "https://api.twilio.com";
+
+/// Most numbers fetched per `IncomingPhoneNumbers` page.
+const PAGE_SIZE: &str = "1000";
+
+/// [`SmsProvider`] for Twilio's Programmable Messaging REST API, or anything
+/// that speaks it.
+pub struct TwilioClient {
+    client: Client,
+    base_url: String,
+    account_sid: String,
+    auth_token: String,
+    status_callback: Option<String>,
+}
+
+fn client(timeouts: &Timeouts) -> AppResult<Client> {
+    let headers = header::HeaderMap::from_iter([(
+        header::ACCEPT,
+        header::HeaderValue::from_static("application/json"),
+    )]);
+    timeouts
+        .client(headers)
+        .map_err(|_| AppError::TwilioApiError("Unable to construct request client".into()))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreateMessage<'a> {
+    to: &'a str,
+    from: &'a str,
+    body: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_callback: Option<&'a str>,
+}
+
+/// The parts of a created message resource we use.
+#[derive(Debug, Deserialize)]
+struct MessageResource {
+    sid: String,
+    to: String,
+    /// Twilio sends this as a string, e.g. `"1"`.
+    #[serde(default)]
+    num_segments: Option<String>,
+    #[serde(default)]
+    price: Option<String>,
+}
+
+/// Body of a failed request.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    code: Option<u32>,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IncomingPhoneNumber {
+    phone_number: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IncomingPhoneNumbersPage {
+    incoming_phone_numbers: Vec<IncomingPhoneNumber>,
+    /// Path of the next page, relative to the base URL.
+    next_page_uri: Option<String>,
+}
+
+impl TwilioClient {
+    pub fn new(account_sid: &str, auth_token: &str, base_url: &str) -> AppResult<Self> {
+        Ok(TwilioClient {
+            client: client(&Timeouts::default())?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            account_sid: account_sid.to_string(),
+            auth_token: auth_token.to_string(),
+            status_callback: None,
+        })
+    }
+
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> AppResult<Self> {
+        self.client = client(&timeouts)?;
+        Ok(self)
+    }
+
+    /// Has Twilio post status updates for every message sent to `url`.
+    pub fn with_status_callback(mut self, url: impl Into<String>) -> Self {
+        self.status_callback = Some(url.into());
+        self
+    }
+
+    /// Phone numbers on the account, which are the only senders it may use.
+    pub async fn fetch_incoming_numbers(&self) -> AppResult<Vec<String>> {
+        let mut numbers = Vec::new();
+        let mut url = format!(
+            "{}?PageSize={}",
+            self.construct_url("IncomingPhoneNumbers.json"),
+            PAGE_SIZE
+        );
+
+        loop {
+            let response = self
+                .client
+                .get(&url)
+                .basic_auth(&self.account_sid, Some(&self.auth_token))
+                .send()
+                .await
+                .map_err(AppError::from_request)?;
+            if !response.status().is_success() {
+                return Err(error_from_response(response, None, None).await);
+            }
+
+            let page: IncomingPhoneNumbersPage = response.json().await.map_err(|err| {
+                AppError::TwilioApiError(format!(
+                    "Unexpected IncomingPhoneNumbers response: {}",
+                    err
+                ))
+            })?;
+            numbers.extend(
+                page.incoming_phone_numbers
+                    .into_iter()
+                    .map(|number| number.phone_number),
+            );
+
+            match page.next_page_uri {
+                Some(next) => url = format!("{}{}", self.base_url, next),
+                None => return Ok(numbers),
+            }
+        }
+    }
+
+    fn construct_url(&self, resource: &str) -> String {
+        format!(
+            "{}/2010-04-01/Accounts/{}/{}",
+            self.base_url, self.account_sid, resource
+        )
+    }
+}
+
+/// Maps Twilio's error codes onto the matching error, so permanent failures
+/// aren't retried.
+async fn error_from_response(res: Response, to: Option<&str>, from: Option<&str>) -> AppError {
+    let status = res.status().as_u16();
+    let body = res
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    let Ok(error) = serde_json::from_str::<ErrorResponse>(&body) else {
+        return AppError::HttpStatus { status, body };
+    };
+
+    let to = to.unwrap_or_default().to_string();
+    let from = from.unwrap_or_default().to_string();
+    match error.code {
+        Some(21211) | Some(21614) => AppError::InvalidPhoneNumber(to),
+        Some(21212) | Some(21606) | Some(21659) => AppError::InvalidSender(from),
+        Some(21408) | Some(21612) => AppError::CountryNotEnabled(to),
+        Some(21610) => AppError::MessageRejected {
+            recipient: to,
+            status: "UNSUBSCRIBED".to_string(),
+        },
+        _ => AppError::HttpStatus {
+            status,
+            body: error.message,
+        },
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsProvider for TwilioClient {
+    fn name(&self) -> &'static str {
+        "twilio"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            bulk_send: false,
+            max_batch_size: 1,
+            // Alphanumeric senders need a Messaging Service
+            alpha_senders: false,
+            delivery_receipts: self.status_callback.is_some(),
+            custom_references: false,
+        }
+    }
+
+    async fn validate_sender(&self, sender: &str) -> AppResult<()> {
+        if validators::validate_e164(sender).is_err() {
+            return Err(AppError::InvalidSender(sender.to_string()));
+        }
+        if !self
+            .fetch_incoming_numbers()
+            .await?
+            .iter()
+            .any(|number| number == sender)
+        {
+            return Err(AppError::InvalidSender(sender.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn send(&self, sms: &OutboundSms) -> AppResult<SentSms> {
+        validators::validate_e164(&sms.to)?;
+
+        let form = CreateMessage {
+            to: &sms.to,
+            from: &sms.from,
+            body: &sms.body,
+            status_callback: self.status_callback.as_deref(),
+        };
+        let response = self
+            .client
+            .post(self.construct_url("Messages.json"))
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&form)
+            .send()
+            .await
+            .map_err(AppError::from_request)?;
+        if !response.status().is_success() {
+            return Err(error_from_response(response, Some(&sms.to), Some(&sms.from)).await);
+        }
+
+        let message: MessageResource = response.json().await.map_err(|err| {
+            AppError::TwilioApiError(format!("Unexpected Messages response: {}", err))
+        })?;
+
+        Ok(SentSms {
+            to: message.to,
+            message_id: Some(message.sid),
+            parts: message.num_segments.and_then(|parts| parts.parse().ok()),
+            price: message.price,
+        })
+    }
+}
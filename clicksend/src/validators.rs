@@ -12,6 +12,144 @@ pub fn validate_e164(phone_number: &str) -> AppResult<()> {
     }
 }
 
+/// Longest alphanumeric sender ID handsets display.
+pub const MAX_ALPHA_TAG_LENGTH: usize = 11;
+
+/// Checks an alphanumeric sender ID is one carriers will deliver: up to
+/// [`MAX_ALPHA_TAG_LENGTH`] ASCII letters, digits, spaces, `-` or `.`, with
+/// at least one letter (all digits would look like a phone number) and no
+/// leading or trailing space.
+pub fn validate_alpha_tag(tag: &str) -> AppResult<()> {
+    let valid = (1..=MAX_ALPHA_TAG_LENGTH).contains(&tag.len())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '.'))
+        && tag.chars().any(|c| c.is_ascii_alphabetic())
+        && tag.trim() == tag;
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InvalidAlphaTag(tag.into()))
+    }
+}
+
+/// The GSM 03.38 default alphabet. Each of these is one septet.
+const GSM7_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+                          ¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+
+/// The GSM 03.38 extension table. These are sent as an escape followed by the
+/// character, so each counts as two septets.
+const GSM7_EXTENSION: &str = "\u{c}^{}\\[~]|€";
+
+/// How a message body will be encoded on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gsm7,
+    Ucs2,
+}
+
+impl Encoding {
+    /// Units that fit in a message sent as a single SMS.
+    fn single_capacity(self) -> usize {
+        match self {
+            Encoding::Gsm7 => 160,
+            Encoding::Ucs2 => 70,
+        }
+    }
+
+    /// Units that fit in each part of a concatenated SMS, after the user data
+    /// header.
+    fn concatenated_capacity(self) -> usize {
+        match self {
+            Encoding::Gsm7 => 153,
+            Encoding::Ucs2 => 67,
+        }
+    }
+}
+
+/// The size of a message body once encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentCount {
+    pub encoding: Encoding,
+    /// Septets for GSM-7, UTF-16 code units for UCS-2.
+    pub units: usize,
+    pub segments: usize,
+}
+
+/// GSM-7 if every character is in the GSM 03.38 alphabet, UCS-2 otherwise.
+pub fn detect_encoding(message: &str) -> Encoding {
+    if message.chars().all(|c| gsm7_width(c).is_some()) {
+        Encoding::Gsm7
+    } else {
+        Encoding::Ucs2
+    }
+}
+
+fn gsm7_width(c: char) -> Option<usize> {
+    if GSM7_BASIC.contains(c) {
+        Some(1)
+    } else if GSM7_EXTENSION.contains(c) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Counts the SMS segments `message` is split into. Characters are never
+/// split across segments: an extension character's escape stays with it, as
+/// does each half of a UTF-16 surrogate pair.
+pub fn count_segments(message: &str) -> SegmentCount {
+    let encoding = detect_encoding(message);
+    let widths: Vec<usize> = message
+        .chars()
+        .map(|c| match encoding {
+            Encoding::Gsm7 => gsm7_width(c).unwrap_or(1),
+            Encoding::Ucs2 => c.len_utf16(),
+        })
+        .collect();
+    let units = widths.iter().sum();
+
+    if units <= encoding.single_capacity() {
+        return SegmentCount {
+            encoding,
+            units,
+            segments: 1,
+        };
+    }
+
+    let capacity = encoding.concatenated_capacity();
+    let mut segments = 1;
+    let mut used = 0;
+    for width in widths {
+        if used + width > capacity {
+            segments += 1;
+            used = 0;
+        }
+        used += width;
+    }
+
+    SegmentCount {
+        encoding,
+        units,
+        segments,
+    }
+}
+
+/// Rejects messages that would be billed as more than `max_segments` parts.
+pub fn validate_segments(message: &str, max_segments: usize) -> AppResult<SegmentCount> {
+    let count = count_segments(message);
+
+    if count.segments > max_segments {
+        return Err(AppError::TooManySegments {
+            segments: count.segments,
+            max: max_segments,
+        });
+    }
+
+    Ok(count)
+}
+
 pub async fn validate_sender_logic<'a, F, G, H, I>(
     sender: &str,
     validate_e164: F,
@@ -46,7 +184,8 @@ where
             return Err(AppError::InvalidSender(sender.to_string()));
         }
     } else {
-        // Check if the sender is a registered Alpha Tag
+        // Check if the sender is a well-formed, registered Alpha Tag
+        validate_alpha_tag(sender)?;
         let alpha_tags = fetch_alpha_tags().await?;
 
         if !alpha_tags.contains(&sender.to_string()) {
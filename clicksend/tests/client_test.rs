@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use clicksend::{
+    circuit::{CircuitBreakerConfig, CircuitState},
+    clicksend::{
+        client::{RetryPolicy, Timeouts},
+        ClickSendApi,
+    },
+    AppError, ClickSendClient,
+};
+use serde_json::json;
+
+/// Counts the requests each stub endpoint has had.
+#[derive(Clone, Default)]
+struct Calls {
+    own_numbers: Arc<AtomicUsize>,
+    numbers: Arc<AtomicUsize>,
+    send: Arc<AtomicUsize>,
+}
+
+/// Answers like ClickSend: `own-numbers` fails twice with 503 before
+/// succeeding, `numbers` is rate limited once, `alpha-tags` has two pages,
+/// and `sms/send` always fails.
+async fn stub_server(calls: Calls) -> String {
+    async fn own_numbers(State(calls): State<Calls>) -> impl IntoResponse {
+        if calls.own_numbers.fetch_add(1, Ordering::SeqCst) < 2 {
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({}))).into_response();
+        }
+        Json(json!({ "own_numbers": [{ "phone_number": "+61400000000" }] })).into_response()
+    }
+
+    async fn numbers(State(calls): State<Calls>) -> impl IntoResponse {
+        if calls.numbers.fetch_add(1, Ordering::SeqCst) == 0 {
+            return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "0")]).into_response();
+        }
+        Json(json!({ "data": { "data": [] } })).into_response()
+    }
+
+    /// Two pages, with a tag of each status.
+    async fn alpha_tags(Query(query): Query<HashMap<String, u32>>) -> impl IntoResponse {
+        let page = query.get("page").copied().unwrap_or(1);
+        let tags = match page {
+            1 => json!([
+                { "alpha_tag": "MYBUSINESS", "status": "approved" },
+                { "alpha_tag": "PENDING", "status": "pending" }
+            ]),
+            _ => json!([
+                { "alpha_tag": "REJECTED", "status": "rejected" },
+                { "alpha_tag": "SECONDPAGE", "status": "APPROVED" }
+            ]),
+        };
+        Json(json!({
+            "http_code": 200,
+            "response_code": "SUCCESS",
+            "data": { "current_page": page, "last_page": 2, "data": tags }
+        }))
+    }
+
+    async fn send(State(calls): State<Calls>) -> impl IntoResponse {
+        calls.send.fetch_add(1, Ordering::SeqCst);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+    }
+
+    let app = Router::new()
+        .route("/v3/own-numbers", get(own_numbers))
+        .route("/v3/numbers", get(numbers))
+        .route("/v3/alpha-tags", get(alpha_tags))
+        .route("/v3/sms/send", post(send))
+        .with_state(calls);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+fn retry_policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        base_delay: Duration::from_millis(5),
+        max_delay: Duration::from_millis(50),
+    }
+}
+
+#[tokio::test]
+async fn test_lookups_are_retried_but_sends_are_not() {
+    let calls = Calls::default();
+    let client = ClickSendClient::new("key", "user", &stub_server(calls.clone()).await, "v3")
+        .unwrap()
+        .with_retry_policy(retry_policy(3));
+
+    let numbers = client.fetch_verified_numbers().await.unwrap();
+    assert_eq!(numbers, vec!["+61400000000"]);
+    assert_eq!(calls.own_numbers.load(Ordering::SeqCst), 3);
+
+    let result = client
+        .send_single_sms("+61400000001", "+61400000000", "Test message")
+        .await;
+    assert!(matches!(
+        result,
+        Err(AppError::HttpStatus { status: 500, .. })
+    ));
+    // The message may have been sent despite the error, so no second attempt
+    assert_eq!(calls.send.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_circuit_opens_after_repeated_failures() {
+    let calls = Calls::default();
+    let client = ClickSendClient::new("key", "user", &stub_server(calls.clone()).await, "v3")
+        .unwrap()
+        .with_retry_policy(retry_policy(1))
+        .with_circuit_breaker(CircuitBreakerConfig {
+            failure_threshold: 2,
+            open_for: Duration::from_secs(60),
+        });
+
+    for _ in 0..2 {
+        assert!(client.fetch_verified_numbers().await.is_err());
+    }
+    assert_eq!(client.circuit_state(), CircuitState::Open);
+
+    let result = client.fetch_verified_numbers().await;
+    assert!(matches!(result, Err(AppError::CircuitOpen)));
+    assert!(result.unwrap_err().is_transient());
+    assert_eq!(calls.own_numbers.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_sender_lists_are_cached_until_invalidated() {
+    let calls = Calls::default();
+    let client = ClickSendClient::new("key", "user", &stub_server(calls.clone()).await, "v3")
+        .unwrap()
+        .with_retry_policy(retry_policy(3));
+
+    for _ in 0..3 {
+        client.validate_sender("+61400000000").await.unwrap();
+    }
+    assert_eq!(calls.own_numbers.load(Ordering::SeqCst), 3);
+    assert_eq!(calls.numbers.load(Ordering::SeqCst), 2);
+
+    client.invalidate_senders();
+    client.validate_sender("+61400000000").await.unwrap();
+    assert_eq!(calls.own_numbers.load(Ordering::SeqCst), 4);
+
+    // Refreshed in the background rather than on the next send
+    let refresh = client.spawn_sender_refresh(Duration::from_millis(20));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    refresh.abort();
+    assert!(calls.own_numbers.load(Ordering::SeqCst) >= 6);
+}
+
+#[tokio::test]
+async fn test_only_approved_alpha_tags_are_accepted() {
+    let client =
+        ClickSendClient::new("key", "user", &stub_server(Calls::default()).await, "v3").unwrap();
+
+    let tags = client.fetch_alpha_tags().await.unwrap();
+    assert_eq!(tags, vec!["MYBUSINESS", "SECONDPAGE"]);
+
+    assert!(client.validate_sender("MYBUSINESS").await.is_ok());
+    assert!(matches!(
+        client.validate_sender("PENDING").await,
+        Err(AppError::InvalidSender(_))
+    ));
+    assert!(matches!(
+        client.validate_sender("TOOLONGALPHATAG").await,
+        Err(AppError::InvalidAlphaTag(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_sender_missing_from_cached_list_is_looked_up_again() {
+    // The number is added to the account after the first lookup
+    async fn own_numbers(State(calls): State<Arc<AtomicUsize>>) -> impl IntoResponse {
+        let numbers = match calls.fetch_add(1, Ordering::SeqCst) {
+            0 => json!([]),
+            _ => json!([{ "phone_number": "+61400000002" }]),
+        };
+        Json(json!({ "own_numbers": numbers }))
+    }
+    async fn numbers() -> impl IntoResponse {
+        Json(json!({ "data": { "data": [] } }))
+    }
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route("/v3/own-numbers", get(own_numbers))
+        .route("/v3/numbers", get(numbers))
+        .with_state(calls.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = ClickSendClient::new("key", "user", &url, "v3").unwrap();
+
+    // Fetched fresh, so there's nothing newer to look for
+    assert!(matches!(
+        client.validate_sender("+61400000002").await,
+        Err(AppError::InvalidSender(_))
+    ));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Missing from the cached list, so the lists are fetched once more
+    client.validate_sender("+61400000002").await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_hung_request_times_out() {
+    async fn hang() -> impl IntoResponse {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Json(json!({}))
+    }
+
+    let app = Router::new().route("/v3/own-numbers", get(hang));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = ClickSendClient::new("key", "user", &url, "v3")
+        .unwrap()
+        .with_retry_policy(retry_policy(1))
+        .with_timeouts(Timeouts {
+            connect: Duration::from_secs(1),
+            request: Duration::from_millis(100),
+        })
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    let err = client.fetch_verified_numbers().await.unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // The request reached ClickSend, which may have acted on it
+    assert!(err.is_transient());
+    assert!(!err.is_unsent());
+}
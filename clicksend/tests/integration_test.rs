@@ -1,4 +1,8 @@
-use clicksend::{api::MessageService, clicksend::mock::MockClickSendClient};
+use clicksend::{
+    api::MessageService,
+    clicksend::{client::OutboundSms, mock::MockClickSendClient},
+    AppError,
+};
 
 #[tokio::test]
 async fn test_send_single_message() {
@@ -9,9 +13,29 @@ async fn test_send_single_message() {
         .send_single_sms("+123456789", "+1234567890", "Test message")
         .await;
 
-    if result.is_err() {
-        println!("Test failed with error: {}", result.as_ref().unwrap_err());
+    if let Err(err) = &result {
+        println!("Test failed with error: {}", err);
     }
 
     assert!(result.is_ok());
 }
+
+#[tokio::test]
+async fn test_send_bulk_messages() {
+    let service = MessageService::new(MockClickSendClient);
+    let sms = |to: &str| OutboundSms {
+        to: to.to_string(),
+        from: "MYBUSINESS".to_string(),
+        body: "Test message".to_string(),
+        custom_string: None,
+    };
+
+    let results = service
+        .send_bulk_sms(&[sms("+61400000001"), sms("0400000002"), sms("+61400000003")])
+        .await;
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().status, "SUCCESS");
+    assert!(matches!(results[1], Err(AppError::InvalidPhoneNumber(_))));
+    assert_eq!(results[2].as_ref().unwrap().to, "+61400000003");
+}
@@ -0,0 +1,49 @@
+use clicksend::{clicksend::client::SmsSendResponse, AppError};
+
+const RESPONSE: &str = r#"{
+    "http_code": 200,
+    "response_code": "SUCCESS",
+    "response_msg": "Messages queued for delivery.",
+    "data": {
+        "total_price": 0.077,
+        "total_count": 3,
+        "queued_count": 1,
+        "messages": [
+            {
+                "to": "+61411111111",
+                "from": "+61400000000",
+                "body": "Hello",
+                "message_id": "BF7AD270-0DE2-418B-B606-71D527D9C1AE",
+                "message_parts": 1,
+                "message_price": "0.0770",
+                "custom_string": "abc",
+                "status": "SUCCESS"
+            },
+            { "to": "+61422222222", "status": "INSUFFICIENT_CREDIT" },
+            { "to": "+61433333333", "status": "SOMETHING_NEW" }
+        ]
+    }
+}"#;
+
+#[test]
+fn test_parse_send_response() {
+    let response: SmsSendResponse = serde_json::from_str(RESPONSE).unwrap();
+    let mut messages = response.data.messages.into_iter();
+
+    let sent = messages.next().unwrap().into_result().unwrap();
+    assert_eq!(
+        sent.message_id.as_deref(),
+        Some("BF7AD270-0DE2-418B-B606-71D527D9C1AE")
+    );
+    assert_eq!(sent.message_parts, Some(1));
+    assert_eq!(sent.message_price.as_deref(), Some("0.0770"));
+
+    assert!(matches!(
+        messages.next().unwrap().into_result(),
+        Err(AppError::InsufficientCredit)
+    ));
+    assert!(matches!(
+        messages.next().unwrap().into_result(),
+        Err(AppError::MessageRejected { status, .. }) if status == "SOMETHING_NEW"
+    ));
+}
@@ -0,0 +1,200 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use clicksend::{
+    circuit::{CircuitBreakerConfig, CircuitState},
+    clicksend::client::OutboundSms,
+    provider::{Capabilities, RouteOptions, SentSms, SmsRouter},
+    AppError, AppResult, SmsProvider,
+};
+
+/// Provider that answers every send with the same result and counts calls.
+struct FakeProvider {
+    error: Option<AppError>,
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl SmsProvider for FakeProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            bulk_send: false,
+            max_batch_size: 1,
+            alpha_senders: true,
+            delivery_receipts: true,
+            custom_references: false,
+        }
+    }
+
+    async fn validate_sender(&self, _sender: &str) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn send(&self, sms: &OutboundSms) -> AppResult<SentSms> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        match &self.error {
+            Some(err) => Err(err.clone()),
+            None => Ok(SentSms {
+                to: sms.to.clone(),
+                message_id: Some(sms.from.clone()),
+                parts: Some(1),
+                price: None,
+            }),
+        }
+    }
+}
+
+fn fake(error: Option<AppError>) -> (Box<dyn SmsProvider>, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let provider = FakeProvider {
+        error,
+        calls: calls.clone(),
+    };
+    (Box::new(provider), calls)
+}
+
+fn route(name: &str, weight: u32, prefixes: &[&str]) -> RouteOptions {
+    RouteOptions {
+        name: name.to_string(),
+        weight,
+        prefixes: prefixes.iter().map(|prefix| prefix.to_string()).collect(),
+        sender: Some(name.to_string()),
+    }
+}
+
+fn sms(to: &str) -> OutboundSms {
+    OutboundSms {
+        to: to.to_string(),
+        from: "MYBUSINESS".to_string(),
+        body: "Test message".to_string(),
+        custom_string: None,
+    }
+}
+
+fn unavailable() -> AppError {
+    AppError::HttpStatus {
+        status: 503,
+        body: "Service Unavailable".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_routes_by_prefix_and_weight() {
+    let (au, _) = fake(None);
+    let (primary, _) = fake(None);
+    let (secondary, _) = fake(None);
+    let router = SmsRouter::new(CircuitBreakerConfig::default())
+        .with_route(au, route("au", 1, &["+61"]))
+        .with_route(primary, route("primary", 3, &[]))
+        .with_route(secondary, route("secondary", 1, &[]));
+
+    let sent = router.send(&sms("+61400000001")).await.unwrap();
+    assert_eq!(sent.message_id.as_deref(), Some("au"));
+
+    let mut primary_count = 0;
+    for _ in 0..8 {
+        let sent = router.send(&sms("+15005550006")).await.unwrap();
+        if sent.message_id.as_deref() == Some("primary") {
+            primary_count += 1;
+        }
+    }
+    assert_eq!(primary_count, 6);
+}
+
+#[tokio::test]
+async fn test_fails_over_when_the_message_cannot_have_gone_out() {
+    let refused = AppError::ConnectionFailed("connection refused".to_string());
+    let (down, down_calls) = fake(Some(refused));
+    let (up, _) = fake(None);
+    let router = SmsRouter::new(CircuitBreakerConfig::default())
+        .with_route(down, route("down", 1, &[]))
+        .with_route(up, route("up", 0, &[]));
+
+    let sent = router.send(&sms("+61400000001")).await.unwrap();
+    assert_eq!(sent.message_id.as_deref(), Some("up"));
+    assert_eq!(down_calls.load(Ordering::SeqCst), 1);
+
+    let (rejecting, _) = fake(Some(AppError::InvalidPhoneNumber("+61".to_string())));
+    let (unused, unused_calls) = fake(None);
+    let router = SmsRouter::new(CircuitBreakerConfig::default())
+        .with_route(rejecting, route("rejecting", 1, &[]))
+        .with_route(unused, route("unused", 0, &[]));
+
+    let result = router.send(&sms("+61400000001")).await;
+    assert!(matches!(result, Err(AppError::InvalidPhoneNumber(_))));
+    assert_eq!(unused_calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_no_failover_after_a_server_error() {
+    let server_error = AppError::HttpStatus {
+        status: 500,
+        body: "Internal Server Error".to_string(),
+    };
+    let (primary, primary_calls) = fake(Some(server_error));
+    let (secondary, secondary_calls) = fake(None);
+    let router = SmsRouter::new(CircuitBreakerConfig::default())
+        .with_route(primary, route("primary", 1, &[]))
+        .with_route(secondary, route("secondary", 0, &[]));
+
+    // The primary may have accepted the message before failing
+    let result = router.send(&sms("+61400000001")).await;
+    assert!(matches!(
+        result,
+        Err(AppError::HttpStatus { status: 500, .. })
+    ));
+    assert_eq!(primary_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(secondary_calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_circuit_opens_after_repeated_failures() {
+    let config = CircuitBreakerConfig {
+        failure_threshold: 2,
+        open_for: Duration::from_millis(50),
+    };
+    let (down, down_calls) = fake(Some(unavailable()));
+    let router = SmsRouter::new(config).with_route(down, route("down", 1, &[]));
+
+    for _ in 0..2 {
+        let result = router.send(&sms("+61400000001")).await;
+        assert!(matches!(
+            result,
+            Err(AppError::HttpStatus { status: 503, .. })
+        ));
+    }
+    assert_eq!(router.health(), vec![("down", CircuitState::Open)]);
+
+    // Refused without calling the provider while open
+    let result = router.send(&sms("+61400000001")).await;
+    assert!(matches!(result, Err(AppError::ProvidersUnavailable(_))));
+    assert!(result.unwrap_err().is_transient());
+    assert_eq!(down_calls.load(Ordering::SeqCst), 2);
+
+    // One trial once the circuit has been open long enough; it fails again
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(router.health(), vec![("down", CircuitState::HalfOpen)]);
+    assert!(router.send(&sms("+61400000001")).await.is_err());
+    assert_eq!(down_calls.load(Ordering::SeqCst), 3);
+    assert_eq!(router.health(), vec![("down", CircuitState::Open)]);
+}
+
+#[tokio::test]
+async fn test_no_route_for_unserved_destination() {
+    let (au, _) = fake(None);
+    let router =
+        SmsRouter::new(CircuitBreakerConfig::default()).with_route(au, route("au", 1, &["+61"]));
+
+    let result = router.send(&sms("+15005550006")).await;
+
+    assert!(matches!(result, Err(AppError::NoRoute(_))));
+}
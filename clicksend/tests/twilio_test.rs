@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::Path,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use clicksend::{clicksend::client::OutboundSms, provider::TwilioClient, AppError, SmsProvider};
+use serde_json::{json, Value};
+
+const ACCOUNT_SID: &str = "AC123";
+/// `AC123:secret`, base64 encoded.
+const AUTHORIZATION: &str = "Basic QUMxMjM6c2VjcmV0";
+
+/// Answers like Twilio's Messages and IncomingPhoneNumbers resources.
+async fn stub_server() -> String {
+    async fn create_message(
+        Path(sid): Path<String>,
+        headers: HeaderMap,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> (StatusCode, Json<Value>) {
+        if sid != ACCOUNT_SID || headers["authorization"] != AUTHORIZATION {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "code": 20003, "message": "Authenticate", "status": 401 })),
+            );
+        }
+        match form["To"].as_str() {
+            "+61400000009" => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "code": 21610,
+                    "message": "Attempt to send to unsubscribed recipient",
+                    "status": 400
+                })),
+            ),
+            "+61400000500" => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({ "code": 20500, "message": "Internal Server Error", "status": 503 })),
+            ),
+            to => (
+                StatusCode::CREATED,
+                Json(json!({
+                    "sid": "SM0001",
+                    "to": to,
+                    "from": form["From"],
+                    "body": form["Body"],
+                    "status": "queued",
+                    "num_segments": "2",
+                    "price": null
+                })),
+            ),
+        }
+    }
+
+    async fn incoming_numbers() -> Json<Value> {
+        Json(json!({
+            "incoming_phone_numbers": [{ "phone_number": "+15005550006" }],
+            "next_page_uri": null
+        }))
+    }
+
+    let app = Router::new()
+        .route(
+            "/2010-04-01/Accounts/:sid/Messages.json",
+            post(create_message),
+        )
+        .route(
+            "/2010-04-01/Accounts/:sid/IncomingPhoneNumbers.json",
+            get(incoming_numbers),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+fn sms(to: &str) -> OutboundSms {
+    OutboundSms {
+        to: to.to_string(),
+        from: "+15005550006".to_string(),
+        body: "Test message".to_string(),
+        custom_string: None,
+    }
+}
+
+#[tokio::test]
+async fn test_send_through_twilio() {
+    let client = TwilioClient::new(ACCOUNT_SID, "secret", &stub_server().await).unwrap();
+    assert_eq!(client.name(), "twilio");
+    assert!(!client.capabilities().bulk_send);
+
+    let sent = client.send(&sms("+61400000001")).await.unwrap();
+    assert_eq!(sent.to, "+61400000001");
+    assert_eq!(sent.message_id.as_deref(), Some("SM0001"));
+    assert_eq!(sent.parts, Some(2));
+
+    let results = client
+        .send_many(&[sms("+61400000002"), sms("0400000003")])
+        .await;
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(AppError::InvalidPhoneNumber(_))));
+}
+
+#[tokio::test]
+async fn test_twilio_errors_are_classified() {
+    let client = TwilioClient::new(ACCOUNT_SID, "secret", &stub_server().await).unwrap();
+
+    let unsubscribed = client.send(&sms("+61400000009")).await.unwrap_err();
+    assert!(matches!(unsubscribed, AppError::MessageRejected { .. }));
+    assert!(!unsubscribed.is_transient());
+
+    let unavailable = client.send(&sms("+61400000500")).await.unwrap_err();
+    assert!(matches!(
+        unavailable,
+        AppError::HttpStatus { status: 503, .. }
+    ));
+    assert!(unavailable.is_transient());
+
+    let unauthorized = TwilioClient::new(ACCOUNT_SID, "wrong", &stub_server().await)
+        .unwrap()
+        .send(&sms("+61400000001"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        unauthorized,
+        AppError::HttpStatus { status: 401, .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_twilio_sender_must_be_an_account_number() {
+    let client = TwilioClient::new(ACCOUNT_SID, "secret", &stub_server().await).unwrap();
+
+    assert!(client.validate_sender("+15005550006").await.is_ok());
+    assert!(matches!(
+        client.validate_sender("+15005550001").await,
+        Err(AppError::InvalidSender(_))
+    ));
+    assert!(matches!(
+        client.validate_sender("MYBUSINESS").await,
+        Err(AppError::InvalidSender(_))
+    ));
+}
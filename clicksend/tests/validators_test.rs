@@ -0,0 +1,83 @@
+use clicksend::{
+    validators::{
+        count_segments, detect_encoding, validate_alpha_tag, validate_segments, Encoding,
+    },
+    AppError,
+};
+
+#[test]
+fn test_gsm7_segments() {
+    assert_eq!(detect_encoding("Hello, world! £5 @ café"), Encoding::Gsm7);
+
+    let single = count_segments(&"a".repeat(160));
+    assert_eq!(
+        (single.encoding, single.units, single.segments),
+        (Encoding::Gsm7, 160, 1)
+    );
+    assert_eq!(count_segments(&"a".repeat(161)).segments, 2);
+    assert_eq!(count_segments(&"a".repeat(306)).segments, 2);
+    assert_eq!(count_segments(&"a".repeat(307)).segments, 3);
+}
+
+#[test]
+fn test_extension_characters_count_double() {
+    let count = count_segments(&"€".repeat(80));
+    assert_eq!(
+        (count.encoding, count.units, count.segments),
+        (Encoding::Gsm7, 160, 1)
+    );
+
+    // The escape and its character are never split: 152 septets leave one
+    // free in the first part, which can't hold a two-septet `{`.
+    let message = format!("{}{{{}", "a".repeat(152), "a".repeat(10));
+    let count = count_segments(&message);
+    assert_eq!(count.units, 164);
+    assert_eq!(count.segments, 2);
+    let message = format!("{}{{{}", "a".repeat(152), "a".repeat(152));
+    assert_eq!(count_segments(&message).segments, 3);
+}
+
+#[test]
+fn test_ucs2_segments() {
+    assert_eq!(detect_encoding("Hello 👋"), Encoding::Ucs2);
+
+    let count = count_segments(&"ū".repeat(69));
+    assert_eq!(
+        (count.encoding, count.units, count.segments),
+        (Encoding::Ucs2, 69, 1)
+    );
+    assert_eq!(count_segments(&"ū".repeat(71)).segments, 2);
+
+    // Emoji are surrogate pairs, two UTF-16 units each
+    let count = count_segments(&"👋".repeat(35));
+    assert_eq!((count.units, count.segments), (70, 1));
+    // 33 fit in each 67-unit part, as a pair is never split
+    assert_eq!(count_segments(&"👋".repeat(66)).segments, 2);
+    assert_eq!(count_segments(&"👋".repeat(67)).segments, 3);
+}
+
+#[test]
+fn test_validate_segments() {
+    assert!(validate_segments("Short message", 1).is_ok());
+
+    match validate_segments(&"👋".repeat(100), 2) {
+        Err(AppError::TooManySegments { segments, max }) => {
+            assert_eq!((segments, max), (4, 2));
+        }
+        other => panic!("expected TooManySegments, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_validate_alpha_tag() {
+    for tag in ["MYBUSINESS", "Acme Co.", "A1-Pharmacy", "X"] {
+        assert!(validate_alpha_tag(tag).is_ok(), "{} should be valid", tag);
+    }
+    for tag in ["", "ALPHAEXAMPLE", "0400000000", "Café", "ACME!", " ACME"] {
+        assert!(
+            matches!(validate_alpha_tag(tag), Err(AppError::InvalidAlphaTag(_))),
+            "{:?} should be invalid",
+            tag
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use lapin::{
+    options::BasicPublishOptions,
+    publisher_confirm::{Confirmation, PublisherConfirm},
+    BasicProperties, Channel,
+};
+
+use crate::error::{AppError, AppResult};
+
+/// AMQP delivery mode marking a message as persistent.
+pub const PERSISTENT: u8 = 2;
+
+/// Publishes `payload` to `queue` on a channel in confirm mode and waits for
+/// the broker to take responsibility for it.
+///
+/// The publish is mandatory, so a message that can't be routed to a queue is
+/// returned by the broker and reported as [`AppError::NotConfirmed`] rather
+/// than silently dropped.
+pub async fn publish_confirmed(
+    channel: &Channel,
+    queue: &str,
+    payload: &[u8],
+    properties: BasicProperties,
+) -> AppResult<()> {
+    let confirm = start_publish(channel, queue, payload, properties).await?;
+    wait_for_confirm(confirm).await
+}
+
+/// Sends a persistent, mandatory publish without waiting for the broker's
+/// confirmation, so many messages can be in flight at once.
+pub async fn start_publish(
+    channel: &Channel,
+    queue: &str,
+    payload: &[u8],
+    properties: BasicProperties,
+) -> AppResult<PublisherConfirm> {
+    let confirm = channel
+        .basic_publish(
+            "",
+            queue,
+            BasicPublishOptions {
+                mandatory: true,
+                ..Default::default()
+            },
+            payload,
+            properties.with_delivery_mode(PERSISTENT),
+        )
+        .await?;
+
+    Ok(confirm)
+}
+
+pub async fn wait_for_confirm(confirm: PublisherConfirm) -> AppResult<()> {
+    match confirm.await? {
+        Confirmation::Ack(None) => Ok(()),
+        _ => Err(AppError::NotConfirmed),
+    }
+}
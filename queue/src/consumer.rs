@@ -0,0 +1,226 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use futures_lite::StreamExt;
+use lapin::{
+    message::Delivery,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions,
+        ConfirmSelectOptions,
+    },
+    types::{AMQPValue, FieldTable, LongString, ShortString},
+    BasicProperties, Channel, Connection, ConnectionProperties,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{confirm::publish_confirmed, error::AppResult, topology::Topology};
+
+/// Header counting how many times a message has been sent to a retry queue.
+pub const RETRY_COUNT_HEADER: &str = "x-retry-count";
+/// Header carrying the error from the most recent failed attempt.
+pub const LAST_ERROR_HEADER: &str = "x-last-error";
+
+/// A queue consumer that yields deserialized messages of type `T`.
+///
+/// Deliveries whose payload can't be deserialized are rejected (without
+/// requeueing) before the error is returned, so a single bad message can't
+/// block the queue. Rejected messages end up on the dead-letter queue.
+pub struct Consumer<T> {
+    _connection: Connection,
+    channel: Channel,
+    topology: Arc<Topology>,
+    inner: lapin::Consumer,
+    _payload: PhantomData<T>,
+}
+
+/// A message received from the queue. It must be explicitly acked, nacked,
+/// requeued, retried or dead-lettered; dropping it leaves the delivery
+/// unacknowledged until the channel closes.
+pub struct Message<T> {
+    pub payload: T,
+    delivery: Delivery,
+    channel: Channel,
+    topology: Arc<Topology>,
+}
+
+/// Where [`Message::retry`] sent a failed message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RetryOutcome {
+    /// Parked on a delay queue; it will be redelivered after the backoff.
+    Scheduled { retry: u32 },
+    /// Out of attempts; moved to the dead-letter queue.
+    DeadLettered,
+}
+
+impl<T: DeserializeOwned> Consumer<T> {
+    /// Connects, declares the queue's topology and starts consuming from it.
+    pub async fn new(amqp_url: &str, topology: Topology, prefetch: u16) -> AppResult<Self> {
+        let connection = Connection::connect(amqp_url, ConnectionProperties::default()).await?;
+
+        let channel = connection.create_channel().await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+
+        topology.declare(&channel).await?;
+
+        channel
+            .basic_qos(prefetch, BasicQosOptions::default())
+            .await?;
+
+        let inner = channel
+            .basic_consume(
+                topology.queue(),
+                "",
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+
+        Ok(Consumer {
+            _connection: connection,
+            channel,
+            topology: Arc::new(topology),
+            inner,
+            _payload: PhantomData,
+        })
+    }
+
+    /// Waits for the next message. Returns `None` once the consumer has been
+    /// cancelled or the channel has closed.
+    pub async fn next(&mut self) -> Option<AppResult<Message<T>>> {
+        let delivery = match self.inner.next().await? {
+            Ok(delivery) => delivery,
+            Err(err) => return Some(Err(err.into())),
+        };
+
+        match serde_json::from_slice(&delivery.data) {
+            Ok(payload) => Some(Ok(Message {
+                payload,
+                delivery,
+                channel: self.channel.clone(),
+                topology: self.topology.clone(),
+            })),
+            Err(err) => {
+                if let Err(nack_err) = delivery
+                    .nack(BasicNackOptions {
+                        requeue: false,
+                        ..Default::default()
+                    })
+                    .await
+                {
+                    return Some(Err(nack_err.into()));
+                }
+                Some(Err(err.into()))
+            }
+        }
+    }
+}
+
+impl<T> Message<T> {
+    pub fn properties(&self) -> &BasicProperties {
+        &self.delivery.properties
+    }
+
+    /// The publisher's unique ID for the message, carried in the AMQP
+    /// `message_id` property and kept across retries.
+    pub fn message_id(&self) -> Option<&str> {
+        self.delivery
+            .properties
+            .message_id()
+            .as_ref()
+            .map(|id| id.as_str())
+    }
+
+    /// Whether the broker has delivered this message before.
+    pub fn redelivered(&self) -> bool {
+        self.delivery.redelivered
+    }
+
+    /// How many times this message has already been retried.
+    pub fn retries(&self) -> u32 {
+        self.delivery
+            .properties
+            .headers()
+            .as_ref()
+            .and_then(|headers| headers.inner().get(RETRY_COUNT_HEADER))
+            .and_then(|value| match value {
+                AMQPValue::LongLongInt(n) => u32::try_from(*n).ok(),
+                AMQPValue::LongInt(n) => u32::try_from(*n).ok(),
+                AMQPValue::LongUInt(n) => Some(*n),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Acknowledges successful processing.
+    pub async fn ack(self) -> AppResult<()> {
+        self.delivery.ack(BasicAckOptions::default()).await?;
+        Ok(())
+    }
+
+    /// Rejects the message without requeueing it.
+    pub async fn nack(self) -> AppResult<()> {
+        self.delivery
+            .nack(BasicNackOptions {
+                requeue: false,
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the message to the queue for immediate redelivery.
+    pub async fn requeue(self) -> AppResult<()> {
+        self.delivery
+            .nack(BasicNackOptions {
+                requeue: true,
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Schedules the message for another attempt after the topology's backoff
+    /// delay, or dead-letters it once the retry policy is exhausted.
+    pub async fn retry(self, error: &str) -> AppResult<RetryOutcome> {
+        let retry = self.retries() + 1;
+
+        if retry > self.topology.max_retries() {
+            self.dead_letter(error).await?;
+            return Ok(RetryOutcome::DeadLettered);
+        }
+
+        let queue = self.topology.retry_queue(retry);
+        self.republish(&queue, retry, error).await?;
+        Ok(RetryOutcome::Scheduled { retry })
+    }
+
+    /// Moves the message to the dead-letter queue, recording `error`.
+    pub async fn dead_letter(self, error: &str) -> AppResult<()> {
+        let queue = self.topology.dead_letter_queue();
+        let retries = self.retries();
+        self.republish(&queue, retries, error).await
+    }
+
+    async fn republish(self, queue: &str, retries: u32, error: &str) -> AppResult<()> {
+        let mut headers = self
+            .delivery
+            .properties
+            .headers()
+            .clone()
+            .unwrap_or_default();
+        headers.insert(
+            ShortString::from(RETRY_COUNT_HEADER),
+            AMQPValue::LongLongInt(retries.into()),
+        );
+        headers.insert(
+            ShortString::from(LAST_ERROR_HEADER),
+            AMQPValue::LongString(LongString::from(error)),
+        );
+        let properties = self.delivery.properties.clone().with_headers(headers);
+
+        publish_confirmed(&self.channel, queue, &self.delivery.data, properties).await?;
+
+        self.ack().await
+    }
+}
@@ -11,4 +11,10 @@ pub enum AppError {
 
     #[error("Serialization error: {0}")]
     SerializationError(#[from] SerdeError),
+
+    #[error("Broker did not confirm the message was stored")]
+    NotConfirmed,
+
+    #[error("Broker unavailable after {0} reconnection attempts")]
+    Unavailable(u32),
 }
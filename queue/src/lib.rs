@@ -1,3 +1,6 @@
+pub mod confirm;
+pub mod consumer;
 pub mod error;
 pub mod publisher;
+pub mod topology;
 pub use error::{AppError, AppResult};
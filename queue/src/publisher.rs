@@ -1,53 +1,247 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use lapin::{
-    options::{BasicPublishOptions, QueueDeclareOptions},
-    types::FieldTable,
-    BasicProperties, Channel, Connection, ConnectionProperties,
+    options::ConfirmSelectOptions, BasicProperties, Channel, Connection, ConnectionProperties,
 };
-use shared::SmsRequest;
+use shared::{InboundSms, QueuedSms};
+use tokio::sync::RwLock;
 
-use crate::error::AppResult;
+use crate::{
+    confirm::{publish_confirmed, start_publish, wait_for_confirm},
+    error::{AppError, AppResult},
+    topology::Topology,
+};
+
+/// How hard the handle tries to re-establish a dropped broker connection
+/// before giving up with [`AppError::Unavailable`].
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    pub max_attempts: u32,
+    /// Delay before the second attempt; doubles after each failure.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The queues a [`RabbitMQ`] handle publishes to.
+#[derive(Clone, Debug)]
+pub struct QueueNames {
+    /// Accepted messages waiting to be sent by a worker.
+    pub outbound: String,
+    /// SMS received through ClickSend.
+    pub inbound: String,
+}
+
+impl Default for QueueNames {
+    fn default() -> Self {
+        QueueNames {
+            outbound: "sms_queue".to_string(),
+            inbound: "sms_inbound".to_string(),
+        }
+    }
+}
+
+struct Link {
+    _connection: Connection,
+    channel: Channel,
+}
+
+/// Publishing as the API does it, so handlers can be exercised without a
+/// broker.
+#[async_trait::async_trait]
+pub trait Publisher: Send + Sync {
+    async fn publish_message(&self, sms: QueuedSms) -> AppResult<()>;
 
+    async fn publish_inbound(&self, sms: &InboundSms) -> AppResult<()>;
+
+    async fn publish_messages(&self, messages: &[QueuedSms]) -> Vec<AppResult<()>>;
+}
+
+#[async_trait::async_trait]
+impl Publisher for RabbitMQ {
+    async fn publish_message(&self, sms: QueuedSms) -> AppResult<()> {
+        RabbitMQ::publish_message(self, sms).await
+    }
+
+    async fn publish_inbound(&self, sms: &InboundSms) -> AppResult<()> {
+        RabbitMQ::publish_inbound(self, sms).await
+    }
+
+    async fn publish_messages(&self, messages: &[QueuedSms]) -> Vec<AppResult<()>> {
+        RabbitMQ::publish_messages(self, messages).await
+    }
+}
+
+/// A cloneable publishing handle. If the connection or channel dies (for
+/// example because the broker restarted), the next publish reconnects,
+/// re-declares the queue topology and retries once.
 #[derive(Clone)]
 pub struct RabbitMQ {
-    pub connection: Arc<Connection>,
-    pub channel: Arc<Channel>,
+    amqp_url: Arc<str>,
+    queues: Arc<QueueNames>,
+    reconnect_policy: ReconnectPolicy,
+    link: Arc<RwLock<Link>>,
 }
 
 impl RabbitMQ {
+    /// Connects, publishing to the default `sms_queue` and `sms_inbound`.
     pub async fn new(amqp_url: &str) -> AppResult<Self> {
-        let connection = Connection::connect(amqp_url, ConnectionProperties::default()).await?;
-
-        let channel = connection.create_channel().await?;
+        Self::with_queues(amqp_url, QueueNames::default()).await
+    }
 
-        channel
-            .queue_declare(
-                "sms_queue",
-                QueueDeclareOptions::default(),
-                FieldTable::default(),
-            )
-            .await?;
+    pub async fn with_queues(amqp_url: &str, queues: QueueNames) -> AppResult<Self> {
+        let link = connect(amqp_url, &queues).await?;
 
         Ok(RabbitMQ {
-            connection: Arc::new(connection),
-            channel: Arc::new(channel),
+            amqp_url: Arc::from(amqp_url),
+            queues: Arc::new(queues),
+            reconnect_policy: ReconnectPolicy::default(),
+            link: Arc::new(RwLock::new(link)),
         })
     }
 
-    pub async fn publish_message(&self, sms: SmsRequest) -> AppResult<()> {
+    pub fn with_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    /// Publishes a persistent message and returns once the broker has
+    /// confirmed it. The message's ID becomes the AMQP `message_id`, so
+    /// consumers can recognise redeliveries.
+    pub async fn publish_message(&self, sms: QueuedSms) -> AppResult<()> {
         let payload = serde_json::to_vec(&sms)?;
 
-        self.channel
-            .basic_publish(
-                "",
-                "sms_queue",
-                BasicPublishOptions::default(),
-                &payload,
-                BasicProperties::default(),
-            )
-            .await?;
+        self.publish(&self.queues.outbound, &payload, with_message_id(&sms.id))
+            .await
+    }
+
+    /// Publishes a received SMS to the inbound queue for downstream consumers.
+    pub async fn publish_inbound(&self, sms: &InboundSms) -> AppResult<()> {
+        let payload = serde_json::to_vec(sms)?;
+
+        self.publish(&self.queues.inbound, &payload, with_message_id(&sms.id))
+            .await
+    }
+
+    /// Publishes many messages, waiting for all of their confirmations at
+    /// the end rather than one at a time. Returns one result per message, in
+    /// order.
+    pub async fn publish_messages(&self, messages: &[QueuedSms]) -> Vec<AppResult<()>> {
+        let channel = match self.channel().await {
+            Ok(channel) => channel,
+            Err(_) => {
+                let attempts = self.reconnect_policy.max_attempts;
+                return messages
+                    .iter()
+                    .map(|_| Err(AppError::Unavailable(attempts)))
+                    .collect();
+            }
+        };
+
+        let mut pending = Vec::with_capacity(messages.len());
+        for sms in messages {
+            let confirm = match serde_json::to_vec(sms) {
+                Ok(payload) => {
+                    let queue = &self.queues.outbound;
+                    start_publish(&channel, queue, &payload, with_message_id(&sms.id)).await
+                }
+                Err(err) => Err(err.into()),
+            };
+            pending.push(confirm);
+        }
 
-        Ok(())
+        let mut results = Vec::with_capacity(pending.len());
+        for confirm in pending {
+            results.push(match confirm {
+                Ok(confirm) => wait_for_confirm(confirm).await,
+                Err(err) => Err(err),
+            });
+        }
+        results
     }
+
+    async fn publish(
+        &self,
+        queue: &str,
+        payload: &[u8],
+        properties: BasicProperties,
+    ) -> AppResult<()> {
+        let channel = self.channel().await?;
+
+        match publish_confirmed(&channel, queue, payload, properties.clone()).await {
+            Err(AppError::ConnectionError(_)) => {
+                let channel = self.reconnect().await?;
+                publish_confirmed(&channel, queue, payload, properties).await
+            }
+            result => result,
+        }
+    }
+
+    /// Returns the current channel, reconnecting first if it has closed.
+    async fn channel(&self) -> AppResult<Channel> {
+        {
+            let link = self.link.read().await;
+            if link.channel.status().connected() {
+                return Ok(link.channel.clone());
+            }
+        }
+
+        self.reconnect().await
+    }
+
+    async fn reconnect(&self) -> AppResult<Channel> {
+        let mut link = self.link.write().await;
+
+        // Another task may have reconnected while we waited for the lock.
+        if link.channel.status().connected() {
+            return Ok(link.channel.clone());
+        }
+
+        let policy = &self.reconnect_policy;
+        let mut delay = policy.initial_delay;
+
+        for attempt in 1..=policy.max_attempts {
+            if attempt > 1 {
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(policy.max_delay);
+            }
+
+            if let Ok(new_link) = connect(&self.amqp_url, &self.queues).await {
+                *link = new_link;
+                return Ok(link.channel.clone());
+            }
+        }
+
+        Err(AppError::Unavailable(policy.max_attempts))
+    }
+}
+
+fn with_message_id(id: &str) -> BasicProperties {
+    BasicProperties::default().with_message_id(id.into())
+}
+
+async fn connect(amqp_url: &str, queues: &QueueNames) -> AppResult<Link> {
+    let connection = Connection::connect(amqp_url, ConnectionProperties::default()).await?;
+
+    let channel = connection.create_channel().await?;
+    channel
+        .confirm_select(ConfirmSelectOptions::default())
+        .await?;
+
+    Topology::new(&queues.outbound).declare(&channel).await?;
+    Topology::new(&queues.inbound).declare(&channel).await?;
+
+    Ok(Link {
+        _connection: connection,
+        channel,
+    })
 }
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use lapin::{
+    options::QueueDeclareOptions,
+    types::{AMQPValue, FieldTable, LongString, ShortString},
+    Channel,
+};
+
+use crate::error::AppResult;
+
+/// How often, and how far apart, a failed message is retried before it is
+/// dead-lettered.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total delivery attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry; each further retry doubles it.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The durable queues backing a work queue: the queue itself, a dead-letter queue
+/// (`<queue>.dlq`) and one delayed retry queue per backoff step
+/// (`<queue>.retry.<delay>ms`).
+///
+/// Retry queues hold messages for their TTL and then dead-letter them back
+/// onto the work queue. Their names include the delay, so publishers and
+/// consumers configured with different retry policies never declare the same
+/// queue with conflicting arguments.
+#[derive(Clone, Debug)]
+pub struct Topology {
+    queue: String,
+    retry_policy: RetryPolicy,
+}
+
+impl Topology {
+    pub fn new(queue: &str) -> Self {
+        Topology {
+            queue: queue.to_string(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn queue(&self) -> &str {
+        &self.queue
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn dead_letter_queue(&self) -> String {
+        format!("{}.dlq", self.queue)
+    }
+
+    /// Delay before retry number `retry` (starting at 1).
+    pub fn retry_delay(&self, retry: u32) -> Duration {
+        self.retry_policy.base_delay * 2u32.saturating_pow(retry.saturating_sub(1))
+    }
+
+    pub fn retry_queue(&self, retry: u32) -> String {
+        format!(
+            "{}.retry.{}ms",
+            self.queue,
+            self.retry_delay(retry).as_millis()
+        )
+    }
+
+    /// Number of retries allowed after the first attempt.
+    pub fn max_retries(&self) -> u32 {
+        self.retry_policy.max_attempts.saturating_sub(1)
+    }
+
+    pub async fn declare(&self, channel: &Channel) -> AppResult<()> {
+        channel
+            .queue_declare(&self.dead_letter_queue(), durable(), FieldTable::default())
+            .await?;
+
+        let mut queue_args = FieldTable::default();
+        dead_letter_to(&mut queue_args, &self.dead_letter_queue());
+        channel
+            .queue_declare(&self.queue, durable(), queue_args)
+            .await?;
+
+        for retry in 1..=self.max_retries() {
+            let mut retry_args = FieldTable::default();
+            dead_letter_to(&mut retry_args, &self.queue);
+            retry_args.insert(
+                ShortString::from("x-message-ttl"),
+                AMQPValue::LongLongInt(self.retry_delay(retry).as_millis() as i64),
+            );
+            channel
+                .queue_declare(&self.retry_queue(retry), durable(), retry_args)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn durable() -> QueueDeclareOptions {
+    QueueDeclareOptions {
+        durable: true,
+        ..Default::default()
+    }
+}
+
+fn dead_letter_to(args: &mut FieldTable, queue: &str) {
+    args.insert(
+        ShortString::from("x-dead-letter-exchange"),
+        AMQPValue::LongString(LongString::from("")),
+    );
+    args.insert(
+        ShortString::from("x-dead-letter-routing-key"),
+        AMQPValue::LongString(LongString::from(queue)),
+    );
+}
@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use queue::topology::{RetryPolicy, Topology};
+
+#[test]
+fn test_retry_delays_double_each_attempt() {
+    let topology = Topology::new("sms_queue").with_retry_policy(RetryPolicy {
+        max_attempts: 4,
+        base_delay: Duration::from_secs(5),
+    });
+
+    assert_eq!(topology.max_retries(), 3);
+    assert_eq!(topology.retry_delay(1), Duration::from_secs(5));
+    assert_eq!(topology.retry_delay(2), Duration::from_secs(10));
+    assert_eq!(topology.retry_delay(3), Duration::from_secs(20));
+}
+
+#[test]
+fn test_queue_names() {
+    let topology = Topology::new("sms_queue");
+
+    assert_eq!(topology.dead_letter_queue(), "sms_queue.dlq");
+    assert_eq!(topology.retry_queue(2), "sms_queue.retry.20000ms");
+}
@@ -1,13 +1,232 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct SmsRequest {
     pub phone_number: String,
     pub message: String,
+    /// When to send the message. Sent straight away when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_at: Option<DateTime<Utc>>,
+}
+
+/// An accepted SMS on its way through `sms_queue`, tagged with the ID it was
+/// stored under.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct QueuedSms {
+    pub id: String,
+    #[serde(flatten)]
+    pub request: SmsRequest,
 }
 
 #[derive(Serialize, Debug)]
 pub struct ApiResponse {
     pub status: u32,
     pub message: String,
+    /// Machine-readable reason, for errors clients are expected to handle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The recipient replied STOP (or similar) and must not be messaged.
+    RecipientSuppressed,
+    /// Too many requests in a short time; see the `Retry-After` header.
+    RateLimited,
+    /// The API key's daily or monthly message quota is used up.
+    QuotaExceeded,
+    /// The `Idempotency-Key` was already used with a different request body.
+    IdempotencyKeyReused,
+    /// A request with the same `Idempotency-Key` hasn't finished yet.
+    RequestInProgress,
+}
+
+/// Why a message to a suppressed recipient was refused, whether by the API
+/// up front or by the worker at send time.
+pub const SUPPRESSED_MESSAGE: &str = "Recipient has opted out of receiving messages";
+
+/// Compares two secrets in time that depends only on their length, so a
+/// mismatch doesn't reveal how much of a guess was right.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Response to a successful `/send_sms`, carrying the ID to query the
+/// message's status with.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SendSmsResponse {
+    pub status: u32,
+    pub message: String,
+    pub id: String,
+}
+
+/// Body of `/send_sms/batch`: either a list of individual messages, or one
+/// message body sent to many recipients.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub enum BatchSmsRequest {
+    Messages {
+        messages: Vec<SmsRequest>,
+    },
+    Broadcast {
+        message: String,
+        phone_numbers: Vec<String>,
+        #[serde(default)]
+        send_at: Option<DateTime<Utc>>,
+    },
+}
+
+impl BatchSmsRequest {
+    pub fn into_requests(self) -> Vec<SmsRequest> {
+        match self {
+            BatchSmsRequest::Messages { messages } => messages,
+            BatchSmsRequest::Broadcast {
+                message,
+                phone_numbers,
+                send_at,
+            } => phone_numbers
+                .into_iter()
+                .map(|phone_number| SmsRequest {
+                    phone_number,
+                    message: message.clone(),
+                    send_at,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Response to `/send_sms/batch`, with one result per submitted message in
+/// the order they were given.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BatchSmsResponse {
+    pub status: u32,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub results: Vec<BatchItemResult>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BatchItemResult {
+    pub phone_number: String,
+    pub accepted: bool,
+    pub id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Response to `GET /messages/{id}`.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MessageStatusResponse {
+    pub id: String,
+    pub phone_number: String,
+    pub status: MessageStatus,
+    /// When a scheduled message is due to be sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_at: Option<DateTime<Utc>>,
+    pub clicksend_message_id: Option<String>,
+    pub error: Option<String>,
+    /// Carrier error code from the delivery receipt, if delivery failed.
+    pub error_code: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub history: Vec<StatusChange>,
+}
+
+/// A reply (or any other inbound SMS) received through ClickSend. This is
+/// both what's published to `sms_inbound` and what `GET /inbound` lists.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct InboundSms {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub body: String,
+    /// ClickSend's ID for the inbound message.
+    pub clicksend_message_id: Option<String>,
+    /// Our ID of the outbound message this replies to, when it could be matched.
+    pub reply_to: Option<String>,
+    pub received_at: DateTime<Utc>,
+}
+
+/// Response to `GET /inbound`, newest first.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InboundListResponse {
+    pub status: u32,
+    pub messages: Vec<InboundSms>,
+}
+
+/// One entry in a message's status history.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct StatusChange {
+    pub status: MessageStatus,
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// Where a message is in its delivery lifecycle.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStatus {
+    /// Stored by the API, not yet on the queue.
+    Accepted,
+    /// Held until its `send_at` time.
+    Scheduled,
+    /// A scheduled message cancelled before it was sent.
+    Cancelled,
+    /// Published to the queue (or waiting there for a retry).
+    Queued,
+    /// Picked up by a worker and being handed to ClickSend.
+    Sending,
+    /// Accepted by ClickSend.
+    Sent,
+    /// Confirmed delivered to the handset.
+    Delivered,
+    /// The carrier reported it could not deliver the message.
+    Undelivered,
+    /// Gave up on the message.
+    Failed,
+}
+
+impl MessageStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageStatus::Accepted => "accepted",
+            MessageStatus::Scheduled => "scheduled",
+            MessageStatus::Cancelled => "cancelled",
+            MessageStatus::Queued => "queued",
+            MessageStatus::Sending => "sending",
+            MessageStatus::Sent => "sent",
+            MessageStatus::Delivered => "delivered",
+            MessageStatus::Undelivered => "undelivered",
+            MessageStatus::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for MessageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MessageStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "accepted" => Ok(MessageStatus::Accepted),
+            "scheduled" => Ok(MessageStatus::Scheduled),
+            "cancelled" => Ok(MessageStatus::Cancelled),
+            "queued" => Ok(MessageStatus::Queued),
+            "sending" => Ok(MessageStatus::Sending),
+            "sent" => Ok(MessageStatus::Sent),
+            "delivered" => Ok(MessageStatus::Delivered),
+            "undelivered" => Ok(MessageStatus::Undelivered),
+            "failed" => Ok(MessageStatus::Failed),
+            other => Err(format!("Unknown message status: {}", other)),
+        }
+    }
 }
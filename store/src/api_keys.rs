@@ -0,0 +1,244 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng, RngCore};
+use rusqlite::{params, types::Type, OptionalExtension, Row};
+use sha2::{Digest, Sha256};
+use shared::constant_time_eq;
+
+use crate::{
+    db::Store,
+    error::{AppError, AppResult},
+};
+
+const KEY_COLUMNS: &str = "id, name, owner, scopes, created_at, expires_at, revoked_at";
+
+/// What an API key is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Send and cancel messages.
+    Send,
+    /// Look up message statuses and inbound messages.
+    ReadStatus,
+    /// Everything.
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Send => "send",
+            Scope::ReadStatus => "read-status",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "send" => Ok(Scope::Send),
+            "read-status" => Ok(Scope::ReadStatus),
+            "admin" => Ok(Scope::Admin),
+            other => Err(format!("Unknown scope: {}", other)),
+        }
+    }
+}
+
+/// A key to create with [`Store::create_api_key`].
+#[derive(Debug, Clone)]
+pub struct NewApiKey {
+    pub name: String,
+    pub owner: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A stored API key. The secret itself is only ever kept as a salted hash.
+#[derive(Debug, Clone)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyRecord {
+    /// Whether the key grants `scope`. Admin keys grant every scope.
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+impl Store {
+    /// Creates a key, returning it along with the token to hand to its user.
+    /// The token can't be recovered later.
+    pub async fn create_api_key(&self, key: NewApiKey) -> AppResult<(ApiKeyRecord, String)> {
+        let record = ApiKeyRecord {
+            id: random_string(12),
+            name: key.name,
+            owner: key.owner,
+            scopes: key.scopes,
+            created_at: Utc::now(),
+            expires_at: key.expires_at,
+            revoked_at: None,
+        };
+        let secret = random_string(32);
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let hash = hash_secret(&salt, &secret);
+        let token = format!("{}.{}", record.id, secret);
+
+        let row = record.clone();
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT INTO api_keys (id, name, owner, scopes, salt, hash, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    row.id,
+                    row.name,
+                    row.owner,
+                    join_scopes(&row.scopes),
+                    hex::encode(salt),
+                    hash,
+                    row.created_at,
+                    row.expires_at
+                ],
+            )?;
+            Ok(())
+        })
+        .await?;
+
+        Ok((record, token))
+    }
+
+    /// Every key, including revoked and expired ones, oldest first.
+    pub async fn list_api_keys(&self) -> AppResult<Vec<ApiKeyRecord>> {
+        self.call(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM api_keys ORDER BY created_at",
+                KEY_COLUMNS
+            ))?;
+            let keys = stmt
+                .query_map([], key_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(keys)
+        })
+        .await
+    }
+
+    /// Revokes a key. Revoking an already revoked key keeps its original
+    /// revocation time.
+    pub async fn revoke_api_key(&self, id: &str) -> AppResult<()> {
+        let id = id.to_string();
+
+        self.call(move |conn| {
+            let updated = conn.execute(
+                "UPDATE api_keys SET revoked_at = COALESCE(revoked_at, ?2) WHERE id = ?1",
+                params![id, Utc::now()],
+            )?;
+            if updated == 0 {
+                return Err(AppError::ApiKeyNotFound(id));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// The active key `token` belongs to, if any.
+    pub async fn authenticate(&self, token: &str) -> AppResult<Option<ApiKeyRecord>> {
+        let Some((id, secret)) = token.split_once('.') else {
+            return Ok(None);
+        };
+        let id = id.to_string();
+        let secret = secret.to_string();
+
+        self.call(move |conn| {
+            let found = conn
+                .query_row(
+                    &format!(
+                        "SELECT {}, salt, hash FROM api_keys WHERE id = ?1",
+                        KEY_COLUMNS
+                    ),
+                    params![id],
+                    |row| {
+                        let salt: String = row.get(7)?;
+                        let hash: String = row.get(8)?;
+                        Ok((key_from_row(row)?, salt, hash))
+                    },
+                )
+                .optional()?;
+
+            let Some((record, salt, hash)) = found else {
+                return Ok(None);
+            };
+            let Ok(salt) = hex::decode(salt) else {
+                return Ok(None);
+            };
+            let matches = constant_time_eq(hash_secret(&salt, &secret).as_bytes(), hash.as_bytes());
+
+            Ok((matches && record.is_active(Utc::now())).then_some(record))
+        })
+        .await
+    }
+}
+
+/// Secrets are long random strings rather than passwords, so a single salted
+/// SHA-256 is enough; there is nothing for a slow hash to protect against.
+fn hash_secret(salt: &[u8], secret: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(secret.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+fn join_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(Scope::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn key_from_row(row: &Row) -> rusqlite::Result<ApiKeyRecord> {
+    let scopes: String = row.get(3)?;
+    let scopes = scopes
+        .split(',')
+        .filter(|scope| !scope.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<Scope>, String>>()
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(3, Type::Text, err.into()))?;
+
+    Ok(ApiKeyRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        owner: row.get(2)?,
+        scopes,
+        created_at: row.get(4)?,
+        expires_at: row.get(5)?,
+        revoked_at: row.get(6)?,
+    })
+}
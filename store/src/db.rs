@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+
+use crate::error::AppResult;
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how
+/// many have run, so only append to this list.
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE messages (
+        id TEXT PRIMARY KEY,
+        phone_number TEXT NOT NULL,
+        message TEXT NOT NULL,
+        status TEXT NOT NULL,
+        clicksend_message_id TEXT,
+        last_error TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+
+    CREATE TABLE message_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        message_id TEXT NOT NULL REFERENCES messages(id),
+        status TEXT NOT NULL,
+        error TEXT,
+        created_at TEXT NOT NULL
+    );
+
+    CREATE INDEX message_events_message_id ON message_events(message_id);
+    "#,
+    r#"
+    ALTER TABLE messages ADD COLUMN error_code TEXT;
+
+    CREATE INDEX messages_clicksend_message_id ON messages(clicksend_message_id);
+    "#,
+    r#"
+    CREATE TABLE inbound_messages (
+        id TEXT PRIMARY KEY,
+        from_number TEXT NOT NULL,
+        to_number TEXT NOT NULL,
+        body TEXT NOT NULL,
+        clicksend_message_id TEXT,
+        reply_to TEXT REFERENCES messages(id),
+        received_at TEXT NOT NULL
+    );
+
+    CREATE INDEX inbound_messages_received_at ON inbound_messages(received_at);
+    "#,
+    r#"
+    CREATE TABLE suppressions (
+        phone_number TEXT PRIMARY KEY,
+        keyword TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    "#,
+    r#"
+    ALTER TABLE messages ADD COLUMN send_at TEXT;
+
+    CREATE INDEX messages_scheduled ON messages(status, send_at);
+    "#,
+    r#"
+    CREATE TABLE api_usage (
+        api_key_id TEXT NOT NULL,
+        period TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (api_key_id, period)
+    );
+    "#,
+    r#"
+    CREATE TABLE api_keys (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        scopes TEXT NOT NULL,
+        salt TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        expires_at TEXT,
+        revoked_at TEXT
+    );
+    "#,
+    r#"
+    CREATE TABLE idempotency_keys (
+        api_key_id TEXT NOT NULL,
+        idempotency_key TEXT NOT NULL,
+        request_hash TEXT NOT NULL,
+        response_status INTEGER,
+        response_body BLOB,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (api_key_id, idempotency_key)
+    );
+    "#,
+    r#"
+    DELETE FROM inbound_messages
+    WHERE clicksend_message_id IS NOT NULL
+      AND rowid NOT IN (
+          SELECT MIN(rowid) FROM inbound_messages
+          WHERE clicksend_message_id IS NOT NULL
+          GROUP BY clicksend_message_id
+      );
+
+    CREATE UNIQUE INDEX inbound_messages_clicksend_id
+        ON inbound_messages(clicksend_message_id);
+    "#,
+    r#"
+    ALTER TABLE messages ADD COLUMN api_key_id TEXT;
+    "#,
+    r#"
+    ALTER TABLE idempotency_keys ADD COLUMN response_headers TEXT;
+
+    CREATE INDEX idempotency_keys_created_at ON idempotency_keys(created_at);
+    "#,
+];
+
+/// SQLite-backed storage shared by the API and the workers.
+///
+/// The connection is used from blocking tasks so queries never stall the
+/// async runtime.
+#[derive(Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    /// Opens (creating if needed) the database at `path` and brings its
+    /// schema up to date.
+    pub fn open(path: &str) -> AppResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> AppResult<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> AppResult<Self> {
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        migrate(&mut conn)?;
+
+        Ok(Store {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` against the connection on the blocking thread pool.
+    pub(crate) async fn call<F, T>(&self, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&mut Connection) -> AppResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+fn migrate(conn: &mut Connection) -> AppResult<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
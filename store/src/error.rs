@@ -0,0 +1,20 @@
+use rusqlite::Error as SqliteError;
+use thiserror::Error;
+use tokio::task::JoinError;
+
+pub type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] SqliteError),
+
+    #[error("Database task failed: {0}")]
+    TaskFailed(#[from] JoinError),
+
+    #[error("Message not found: {0}")]
+    NotFound(String),
+
+    #[error("API key not found: {0}")]
+    ApiKeyNotFound(String),
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, OptionalExtension};
+
+use crate::{db::Store, error::AppResult};
+
+/// How long a claimed key may go without a response before its request is
+/// assumed to have died (say, with the server restarting) and the key can be
+/// claimed again.
+const ABANDONED_AFTER_SECS: i64 = 5 * 60;
+
+/// Result of [`Store::claim_idempotency_key`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdempotencyClaim {
+    /// First use of the key: handle the request, then save the response with
+    /// [`Store::complete_idempotency_key`].
+    New,
+    /// The request was already handled; this is the response it got.
+    Replay {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    },
+    /// The same request is still being handled.
+    InProgress,
+    /// The key was used for a different request.
+    Mismatch,
+}
+
+impl Store {
+    /// Claims an idempotency key for a request whose body hashes to
+    /// `request_hash`. Uses older than `window` are forgotten, as are claims
+    /// that never got a response.
+    pub async fn claim_idempotency_key(
+        &self,
+        api_key_id: &str,
+        idempotency_key: &str,
+        request_hash: &str,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> AppResult<IdempotencyClaim> {
+        let api_key_id = api_key_id.to_string();
+        let idempotency_key = idempotency_key.to_string();
+        let request_hash = request_hash.to_string();
+
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM idempotency_keys WHERE created_at < ?1",
+                params![now - window],
+            )?;
+            tx.execute(
+                "DELETE FROM idempotency_keys
+                 WHERE api_key_id = ?1 AND idempotency_key = ?2
+                   AND response_status IS NULL AND created_at < ?3",
+                params![
+                    api_key_id,
+                    idempotency_key,
+                    now - Duration::seconds(ABANDONED_AFTER_SECS)
+                ],
+            )?;
+
+            let existing = tx
+                .query_row(
+                    "SELECT request_hash, response_status, response_headers, response_body
+                     FROM idempotency_keys
+                     WHERE api_key_id = ?1 AND idempotency_key = ?2",
+                    params![api_key_id, idempotency_key],
+                    |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, Option<u16>>(1)?,
+                            row.get::<_, Option<String>>(2)?,
+                            row.get::<_, Option<Vec<u8>>>(3)?,
+                        ))
+                    },
+                )
+                .optional()?;
+
+            let claim = match existing {
+                Some((hash, _, _, _)) if hash != request_hash => IdempotencyClaim::Mismatch,
+                Some((_, Some(status), headers, body)) => IdempotencyClaim::Replay {
+                    status,
+                    headers: headers.as_deref().map(parse_headers).unwrap_or_default(),
+                    body: body.unwrap_or_default(),
+                },
+                Some(_) => IdempotencyClaim::InProgress,
+                None => {
+                    tx.execute(
+                        "INSERT INTO idempotency_keys
+                             (api_key_id, idempotency_key, request_hash, created_at)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        params![api_key_id, idempotency_key, request_hash, now],
+                    )?;
+                    IdempotencyClaim::New
+                }
+            };
+            tx.commit()?;
+            Ok(claim)
+        })
+        .await
+    }
+
+    /// Saves the response to replay for a claimed key.
+    pub async fn complete_idempotency_key(
+        &self,
+        api_key_id: &str,
+        idempotency_key: &str,
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> AppResult<()> {
+        let api_key_id = api_key_id.to_string();
+        let idempotency_key = idempotency_key.to_string();
+        let headers = format_headers(&headers);
+
+        self.call(move |conn| {
+            conn.execute(
+                "UPDATE idempotency_keys
+                 SET response_status = ?3, response_headers = ?4, response_body = ?5
+                 WHERE api_key_id = ?1 AND idempotency_key = ?2",
+                params![api_key_id, idempotency_key, status, headers, body],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Gives up a claimed key, so a retry of the request is handled afresh.
+    pub async fn release_idempotency_key(
+        &self,
+        api_key_id: &str,
+        idempotency_key: &str,
+    ) -> AppResult<()> {
+        let api_key_id = api_key_id.to_string();
+        let idempotency_key = idempotency_key.to_string();
+
+        self.call(move |conn| {
+            conn.execute(
+                "DELETE FROM idempotency_keys WHERE api_key_id = ?1 AND idempotency_key = ?2",
+                params![api_key_id, idempotency_key],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Headers are kept one `name: value` per line, as on the wire; header
+/// values can't contain line breaks.
+fn format_headers(headers: &[(String, String)]) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\n", name, value))
+        .collect()
+}
+
+fn parse_headers(headers: &str) -> Vec<(String, String)> {
+    headers
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
@@ -0,0 +1,116 @@
+use chrono::Utc;
+use rusqlite::{params, Row};
+use shared::InboundSms;
+use uuid::Uuid;
+
+use crate::{db::Store, error::AppResult};
+
+/// An inbound SMS as received, before it's given an ID.
+#[derive(Debug, Clone)]
+pub struct NewInbound {
+    pub from: String,
+    pub to: String,
+    pub body: String,
+    pub clicksend_message_id: Option<String>,
+    pub reply_to: Option<String>,
+}
+
+/// Outcome of [`Store::insert_inbound`].
+#[derive(Debug, Clone)]
+pub enum Received {
+    /// First time this message was seen; it's now stored.
+    New(InboundSms),
+    /// ClickSend delivered this message before; here's the stored copy.
+    Duplicate(InboundSms),
+}
+
+const INBOUND_COLUMNS: &str =
+    "id, from_number, to_number, body, clicksend_message_id, reply_to, received_at";
+
+fn inbound_from_row(row: &Row) -> rusqlite::Result<InboundSms> {
+    Ok(InboundSms {
+        id: row.get(0)?,
+        from: row.get(1)?,
+        to: row.get(2)?,
+        body: row.get(3)?,
+        clicksend_message_id: row.get(4)?,
+        reply_to: row.get(5)?,
+        received_at: row.get(6)?,
+    })
+}
+
+impl Store {
+    /// Stores a received SMS under a freshly generated ID, unless one with
+    /// the same ClickSend message ID is already stored.
+    pub async fn insert_inbound(&self, inbound: NewInbound) -> AppResult<Received> {
+        let record = InboundSms {
+            id: Uuid::new_v4().to_string(),
+            from: inbound.from,
+            to: inbound.to,
+            body: inbound.body,
+            clicksend_message_id: inbound.clicksend_message_id,
+            reply_to: inbound.reply_to,
+            received_at: Utc::now(),
+        };
+
+        self.call(move |conn| {
+            let inserted = conn.execute(
+                &format!(
+                    "INSERT INTO inbound_messages ({})
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                     ON CONFLICT (clicksend_message_id) DO NOTHING",
+                    INBOUND_COLUMNS
+                ),
+                params![
+                    record.id,
+                    record.from,
+                    record.to,
+                    record.body,
+                    record.clicksend_message_id,
+                    record.reply_to,
+                    record.received_at
+                ],
+            )?;
+            if inserted > 0 {
+                return Ok(Received::New(record));
+            }
+
+            // Only a ClickSend message ID can conflict, so a copy exists
+            let existing = conn.query_row(
+                &format!(
+                    "SELECT {} FROM inbound_messages WHERE clicksend_message_id = ?1",
+                    INBOUND_COLUMNS
+                ),
+                params![record.clicksend_message_id],
+                inbound_from_row,
+            )?;
+            Ok(Received::Duplicate(existing))
+        })
+        .await
+    }
+
+    /// The most recently received messages, newest first. With an
+    /// `api_key_id`, only replies to messages sent with that key.
+    pub async fn list_inbound(
+        &self,
+        api_key_id: Option<&str>,
+        limit: u32,
+    ) -> AppResult<Vec<InboundSms>> {
+        let api_key_id = api_key_id.map(str::to_string);
+
+        self.call(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM inbound_messages
+                 WHERE ?2 IS NULL
+                    OR reply_to IN (SELECT id FROM messages WHERE api_key_id = ?2)
+                 ORDER BY received_at DESC LIMIT ?1",
+                INBOUND_COLUMNS
+            ))?;
+            let messages = stmt
+                .query_map(params![limit, api_key_id], inbound_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(messages)
+        })
+        .await
+    }
+}
@@ -0,0 +1,16 @@
+pub mod api_keys;
+pub mod db;
+pub mod error;
+pub mod idempotency;
+pub mod inbound;
+pub mod messages;
+pub mod suppressions;
+pub mod usage;
+
+pub use api_keys::{ApiKeyRecord, NewApiKey, Scope};
+pub use db::Store;
+pub use error::{AppError, AppResult};
+pub use idempotency::IdempotencyClaim;
+pub use inbound::{NewInbound, Received};
+pub use messages::{MessageRecord, StatusEvent, StatusUpdate};
+pub use usage::{QuotaLimits, QuotaUsage, Reservation};
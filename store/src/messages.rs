@@ -0,0 +1,461 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, types::Type, OptionalExtension, Row, Transaction};
+use shared::{MessageStatus, QueuedSms, SmsRequest};
+use uuid::Uuid;
+
+use crate::{
+    db::Store,
+    error::{AppError, AppResult},
+};
+
+/// Columns read by [`message_from_row`], in order.
+const MESSAGE_COLUMNS: &str = "id, phone_number, message, status, clicksend_message_id, \
+     last_error, error_code, send_at, created_at, updated_at, api_key_id";
+
+/// A stored outbound message and its current status.
+#[derive(Debug, Clone)]
+pub struct MessageRecord {
+    pub id: String,
+    pub phone_number: String,
+    pub message: String,
+    pub status: MessageStatus,
+    pub clicksend_message_id: Option<String>,
+    pub last_error: Option<String>,
+    pub error_code: Option<String>,
+    pub send_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The API key the message was sent with. Messages stored before keys
+    /// were recorded have none.
+    pub api_key_id: Option<String>,
+}
+
+impl MessageRecord {
+    /// The message as it is published to `sms_queue`.
+    pub fn to_queued(&self) -> QueuedSms {
+        QueuedSms {
+            id: self.id.clone(),
+            request: SmsRequest {
+                phone_number: self.phone_number.clone(),
+                message: self.message.clone(),
+                send_at: self.send_at,
+            },
+        }
+    }
+}
+
+/// A status change to apply with [`Store::set_statuses`].
+#[derive(Debug, Clone)]
+pub struct StatusUpdate {
+    pub id: String,
+    pub status: MessageStatus,
+    pub error: Option<String>,
+}
+
+/// One status transition in a message's history.
+#[derive(Debug, Clone)]
+pub struct StatusEvent {
+    pub status: MessageStatus,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Store {
+    /// Stores a newly accepted message, sent with `api_key_id`, under a
+    /// freshly generated ID.
+    pub async fn insert_message(
+        &self,
+        sms: &SmsRequest,
+        api_key_id: &str,
+    ) -> AppResult<MessageRecord> {
+        let mut records = self
+            .insert_messages(std::slice::from_ref(sms), api_key_id)
+            .await?;
+        Ok(records.remove(0))
+    }
+
+    /// Stores several accepted messages in a single transaction. Messages
+    /// with a `send_at` time start out [`MessageStatus::Scheduled`].
+    pub async fn insert_messages(
+        &self,
+        messages: &[SmsRequest],
+        api_key_id: &str,
+    ) -> AppResult<Vec<MessageRecord>> {
+        let now = Utc::now();
+        let records: Vec<MessageRecord> = messages
+            .iter()
+            .map(|sms| MessageRecord {
+                id: Uuid::new_v4().to_string(),
+                phone_number: sms.phone_number.clone(),
+                message: sms.message.clone(),
+                status: match sms.send_at {
+                    Some(_) => MessageStatus::Scheduled,
+                    None => MessageStatus::Accepted,
+                },
+                clicksend_message_id: None,
+                last_error: None,
+                error_code: None,
+                send_at: sms.send_at,
+                created_at: now,
+                updated_at: now,
+                api_key_id: Some(api_key_id.to_string()),
+            })
+            .collect();
+
+        let rows = records.clone();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut insert_message = tx.prepare(
+                    "INSERT INTO messages
+                        (id, phone_number, message, status, send_at, created_at, updated_at,
+                         api_key_id)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7)",
+                )?;
+                let mut insert_event = tx.prepare(
+                    "INSERT INTO message_events (message_id, status, created_at) VALUES (?1, ?2, ?3)",
+                )?;
+                for row in &rows {
+                    insert_message.execute(params![
+                        row.id,
+                        row.phone_number,
+                        row.message,
+                        row.status.as_str(),
+                        row.send_at,
+                        row.created_at,
+                        row.api_key_id
+                    ])?;
+                    insert_event.execute(params![row.id, row.status.as_str(), row.created_at])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Moves a message to `status`, recording the transition. An `error`
+    /// becomes the message's `last_error` and is kept on the event.
+    pub async fn set_status(
+        &self,
+        id: &str,
+        status: MessageStatus,
+        error: Option<&str>,
+    ) -> AppResult<()> {
+        self.set_statuses(vec![StatusUpdate {
+            id: id.to_string(),
+            status,
+            error: error.map(str::to_string),
+        }])
+        .await
+    }
+
+    /// Applies several status changes in a single transaction. Fails, changing
+    /// nothing, if any of the messages doesn't exist.
+    pub async fn set_statuses(&self, updates: Vec<StatusUpdate>) -> AppResult<()> {
+        self.call(move |conn| {
+            let now = Utc::now();
+            let tx = conn.transaction()?;
+            for update in &updates {
+                apply_status(&tx, update, now)?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Marks a message as accepted by ClickSend, keeping ClickSend's ID for it
+    /// so delivery receipts can be matched up later. A receipt that raced
+    /// ahead has already settled the message, so its status is kept.
+    pub async fn mark_sent(&self, id: &str, clicksend_message_id: Option<&str>) -> AppResult<()> {
+        let update = StatusUpdate {
+            id: id.to_string(),
+            status: MessageStatus::Sent,
+            error: None,
+        };
+        let clicksend_message_id = clicksend_message_id.map(str::to_string);
+
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let status: Option<String> = tx
+                .query_row(
+                    "SELECT status FROM messages WHERE id = ?1",
+                    params![update.id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let delivered = [MessageStatus::Delivered, MessageStatus::Undelivered]
+                .map(|status| status.as_str());
+            if !status.is_some_and(|status| delivered.contains(&status.as_str())) {
+                apply_status(&tx, &update, Utc::now())?;
+            }
+            tx.execute(
+                "UPDATE messages SET clicksend_message_id = ?2 WHERE id = ?1",
+                params![update.id, clicksend_message_id],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Finds the message a ClickSend callback refers to: by ClickSend's
+    /// message ID, or failing that by the custom string it was sent with
+    /// (our own message ID).
+    pub async fn find_by_clicksend_reference(
+        &self,
+        clicksend_message_id: Option<&str>,
+        custom_string: Option<&str>,
+    ) -> AppResult<Option<MessageRecord>> {
+        let clicksend_message_id = clicksend_message_id.map(str::to_string);
+        let custom_string = custom_string.map(str::to_string);
+
+        self.call(move |conn| {
+            let record = conn
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM messages
+                         WHERE clicksend_message_id = ?1 OR id = ?2
+                         ORDER BY clicksend_message_id = ?1 DESC
+                         LIMIT 1",
+                        MESSAGE_COLUMNS
+                    ),
+                    params![clicksend_message_id, custom_string],
+                    message_from_row,
+                )
+                .optional()?;
+            Ok(record)
+        })
+        .await
+    }
+
+    /// Applies a delivery receipt: moves the message to `status` and keeps
+    /// the carrier's error code and text, if any.
+    pub async fn record_delivery(
+        &self,
+        id: &str,
+        status: MessageStatus,
+        error_code: Option<&str>,
+        error: Option<&str>,
+    ) -> AppResult<()> {
+        let update = StatusUpdate {
+            id: id.to_string(),
+            status,
+            error: error.map(str::to_string),
+        };
+        let error_code = error_code.map(str::to_string);
+
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            apply_status(&tx, &update, Utc::now())?;
+            tx.execute(
+                "UPDATE messages SET error_code = ?2 WHERE id = ?1",
+                params![update.id, error_code],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Claims up to `limit` scheduled messages due by `now`, oldest first,
+    /// moving them to [`MessageStatus::Queued`] so they are neither claimed
+    /// twice nor cancelled while being published.
+    ///
+    /// Scheduled messages that have sat in `queued` for `reclaim_after` are
+    /// claimed again: their claimer most likely died before publishing them.
+    /// Set it well above how long the queue takes to drain, as a message
+    /// still waiting in the queue would be published twice.
+    pub async fn claim_due_messages(
+        &self,
+        now: DateTime<Utc>,
+        reclaim_after: Duration,
+        limit: u32,
+    ) -> AppResult<Vec<MessageRecord>> {
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let mut records = {
+                let mut stmt = tx.prepare(&format!(
+                    "SELECT {} FROM messages
+                     WHERE (status = ?1 AND send_at <= ?2)
+                        OR (status = ?3 AND send_at IS NOT NULL AND updated_at <= ?4)
+                     ORDER BY send_at LIMIT ?5",
+                    MESSAGE_COLUMNS
+                ))?;
+                let rows = stmt.query_map(
+                    params![
+                        MessageStatus::Scheduled.as_str(),
+                        now,
+                        MessageStatus::Queued.as_str(),
+                        now.checked_sub_signed(reclaim_after)
+                            .unwrap_or(DateTime::<Utc>::MIN_UTC),
+                        limit
+                    ],
+                    message_from_row,
+                )?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            };
+
+            let claimed_at = Utc::now();
+            for record in &mut records {
+                let update = StatusUpdate {
+                    id: record.id.clone(),
+                    status: MessageStatus::Queued,
+                    error: None,
+                };
+                apply_status(&tx, &update, claimed_at)?;
+                record.status = MessageStatus::Queued;
+                record.updated_at = claimed_at;
+            }
+            tx.commit()?;
+            Ok(records)
+        })
+        .await
+    }
+
+    /// Cancels a message that is still waiting for its `send_at` time.
+    /// Returns whether it was; `false` means it has already gone out (or
+    /// wasn't scheduled at all).
+    pub async fn cancel_scheduled(&self, id: &str) -> AppResult<bool> {
+        let id = id.to_string();
+
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let status = tx
+                .query_row(
+                    "SELECT status FROM messages WHERE id = ?1",
+                    params![id],
+                    |row| status_column(row, 0),
+                )
+                .optional()?;
+            match status {
+                None => return Err(AppError::NotFound(id)),
+                Some(MessageStatus::Scheduled) => {}
+                Some(_) => return Ok(false),
+            }
+
+            let update = StatusUpdate {
+                id,
+                status: MessageStatus::Cancelled,
+                error: None,
+            };
+            apply_status(&tx, &update, Utc::now())?;
+            tx.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Whether ClickSend has already accepted the message, so sending it
+    /// again would deliver a duplicate.
+    pub async fn is_sent(&self, id: &str) -> AppResult<bool> {
+        let id = id.to_string();
+
+        self.call(move |conn| {
+            let sent = conn
+                .query_row(
+                    "SELECT 1 FROM messages
+                     WHERE id = ?1
+                       AND (status IN (?2, ?3, ?4) OR clicksend_message_id IS NOT NULL)",
+                    params![
+                        id,
+                        MessageStatus::Sent.as_str(),
+                        MessageStatus::Delivered.as_str(),
+                        MessageStatus::Undelivered.as_str()
+                    ],
+                    |_| Ok(()),
+                )
+                .optional()?;
+            Ok(sent.is_some())
+        })
+        .await
+    }
+
+    pub async fn get_message(&self, id: &str) -> AppResult<Option<MessageRecord>> {
+        let id = id.to_string();
+
+        self.call(move |conn| {
+            let record = conn
+                .query_row(
+                    &format!("SELECT {} FROM messages WHERE id = ?1", MESSAGE_COLUMNS),
+                    params![id],
+                    message_from_row,
+                )
+                .optional()?;
+            Ok(record)
+        })
+        .await
+    }
+
+    /// The message's status history, oldest first.
+    pub async fn message_events(&self, id: &str) -> AppResult<Vec<StatusEvent>> {
+        let id = id.to_string();
+
+        self.call(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT status, error, created_at FROM message_events
+                 WHERE message_id = ?1 ORDER BY id",
+            )?;
+            let events = stmt
+                .query_map(params![id], |row| {
+                    Ok(StatusEvent {
+                        status: status_column(row, 0)?,
+                        error: row.get(1)?,
+                        created_at: row.get(2)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(events)
+        })
+        .await
+    }
+}
+
+fn apply_status(tx: &Transaction, update: &StatusUpdate, now: DateTime<Utc>) -> AppResult<()> {
+    let status = update.status.as_str();
+
+    let updated = tx
+        .prepare_cached(
+            "UPDATE messages
+             SET status = ?2, last_error = COALESCE(?3, last_error), updated_at = ?4
+             WHERE id = ?1",
+        )?
+        .execute(params![update.id, status, update.error, now])?;
+    if updated == 0 {
+        return Err(AppError::NotFound(update.id.clone()));
+    }
+
+    tx.prepare_cached(
+        "INSERT INTO message_events (message_id, status, error, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+    )?
+    .execute(params![update.id, status, update.error, now])?;
+
+    Ok(())
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<MessageRecord> {
+    Ok(MessageRecord {
+        id: row.get(0)?,
+        phone_number: row.get(1)?,
+        message: row.get(2)?,
+        status: status_column(row, 3)?,
+        clicksend_message_id: row.get(4)?,
+        last_error: row.get(5)?,
+        error_code: row.get(6)?,
+        send_at: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        api_key_id: row.get(10)?,
+    })
+}
+
+fn status_column(row: &Row, index: usize) -> rusqlite::Result<MessageStatus> {
+    let value: String = row.get(index)?;
+    value.parse().map_err(|err: String| {
+        rusqlite::Error::FromSqlConversionFailure(index, Type::Text, err.into())
+    })
+}
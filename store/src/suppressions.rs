@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use rusqlite::{params, OptionalExtension};
+
+use crate::{db::Store, error::AppResult};
+
+impl Store {
+    /// Adds a number to the suppression list, recording the keyword that
+    /// opted it out. Suppressing an already suppressed number is a no-op.
+    pub async fn suppress(&self, phone_number: &str, keyword: &str) -> AppResult<()> {
+        let phone_number = phone_number.to_string();
+        let keyword = keyword.to_string();
+
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO suppressions (phone_number, keyword, created_at)
+                 VALUES (?1, ?2, ?3)",
+                params![phone_number, keyword, Utc::now()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Removes a number from the suppression list.
+    pub async fn unsuppress(&self, phone_number: &str) -> AppResult<()> {
+        let phone_number = phone_number.to_string();
+
+        self.call(move |conn| {
+            conn.execute(
+                "DELETE FROM suppressions WHERE phone_number = ?1",
+                params![phone_number],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn is_suppressed(&self, phone_number: &str) -> AppResult<bool> {
+        let phone_number = phone_number.to_string();
+
+        self.call(move |conn| {
+            let found = conn
+                .query_row(
+                    "SELECT 1 FROM suppressions WHERE phone_number = ?1",
+                    params![phone_number],
+                    |_| Ok(()),
+                )
+                .optional()?;
+            Ok(found.is_some())
+        })
+        .await
+    }
+
+    /// Which of `phone_numbers` are suppressed.
+    pub async fn suppressed_among(&self, phone_numbers: Vec<String>) -> AppResult<HashSet<String>> {
+        self.call(move |conn| {
+            let mut stmt =
+                conn.prepare_cached("SELECT 1 FROM suppressions WHERE phone_number = ?1")?;
+            let mut suppressed = HashSet::new();
+            for phone_number in phone_numbers {
+                if stmt.exists(params![phone_number])? {
+                    suppressed.insert(phone_number);
+                }
+            }
+            Ok(suppressed)
+        })
+        .await
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{db::Store, error::AppResult};
+
+/// Most messages an API key may send per UTC day and month. `None` means
+/// unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuotaLimits {
+    pub daily: Option<u32>,
+    pub monthly: Option<u32>,
+}
+
+/// Messages an API key has sent in the current UTC day and month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub daily: u32,
+    pub monthly: u32,
+}
+
+impl QuotaUsage {
+    /// Whether `count` more messages fit within `limits`.
+    pub fn allows(&self, count: u32, limits: &QuotaLimits) -> bool {
+        let fits = |used: u32, limit: Option<u32>| {
+            limit.is_none_or(|limit| used.saturating_add(count) <= limit)
+        };
+        fits(self.daily, limits.daily) && fits(self.monthly, limits.monthly)
+    }
+}
+
+/// Result of [`Store::reserve_quota`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reservation {
+    /// The messages were counted; this is the usage including them.
+    Reserved(QuotaUsage),
+    /// The messages didn't fit and nothing was counted; this is the usage as
+    /// it stands.
+    Exceeded(QuotaUsage),
+}
+
+impl Store {
+    pub async fn quota_usage(&self, api_key_id: &str, now: DateTime<Utc>) -> AppResult<QuotaUsage> {
+        let api_key_id = api_key_id.to_string();
+
+        self.call(move |conn| usage(conn, &api_key_id, now)).await
+    }
+
+    /// Counts `count` messages against the key's quotas, unless that would
+    /// exceed `limits`.
+    pub async fn reserve_quota(
+        &self,
+        api_key_id: &str,
+        count: u32,
+        limits: QuotaLimits,
+        now: DateTime<Utc>,
+    ) -> AppResult<Reservation> {
+        let api_key_id = api_key_id.to_string();
+
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let current = usage(&tx, &api_key_id, now)?;
+            if !current.allows(count, &limits) {
+                return Ok(Reservation::Exceeded(current));
+            }
+
+            for period in [day(now), month(now)] {
+                tx.execute(
+                    "INSERT INTO api_usage (api_key_id, period, count) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (api_key_id, period) DO UPDATE SET count = count + ?3",
+                    params![api_key_id, period, count],
+                )?;
+            }
+            tx.commit()?;
+
+            Ok(Reservation::Reserved(QuotaUsage {
+                daily: current.daily + count,
+                monthly: current.monthly + count,
+            }))
+        })
+        .await
+    }
+}
+
+fn usage(conn: &Connection, api_key_id: &str, now: DateTime<Utc>) -> AppResult<QuotaUsage> {
+    let count = |period: String| -> AppResult<u32> {
+        let count = conn
+            .query_row(
+                "SELECT count FROM api_usage WHERE api_key_id = ?1 AND period = ?2",
+                params![api_key_id, period],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(count.unwrap_or(0))
+    };
+
+    Ok(QuotaUsage {
+        daily: count(day(now))?,
+        monthly: count(month(now))?,
+    })
+}
+
+fn day(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m-%d").to_string()
+}
+
+fn month(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m").to_string()
+}
@@ -0,0 +1,47 @@
+use chrono::{Duration, Utc};
+use store::{NewApiKey, Scope, Store};
+
+fn new_key() -> NewApiKey {
+    NewApiKey {
+        name: "billing".to_string(),
+        owner: "ops@example.com".to_string(),
+        scopes: vec![Scope::Send],
+        expires_at: None,
+    }
+}
+
+#[tokio::test]
+async fn test_authenticate_and_revoke() {
+    let store = Store::open_in_memory().unwrap();
+    let (record, token) = store.create_api_key(new_key()).await.unwrap();
+
+    let found = store.authenticate(&token).await.unwrap().unwrap();
+    assert_eq!(found.id, record.id);
+    assert!(found.allows(Scope::Send));
+    assert!(!found.allows(Scope::ReadStatus));
+
+    // Right key ID, wrong secret
+    let forged = format!("{}.{}", record.id, "x".repeat(32));
+    assert!(store.authenticate(&forged).await.unwrap().is_none());
+    assert!(store.authenticate("not-a-token").await.unwrap().is_none());
+
+    store.revoke_api_key(&record.id).await.unwrap();
+    assert!(store.authenticate(&token).await.unwrap().is_none());
+    let keys = store.list_api_keys().await.unwrap();
+    assert!(keys[0].revoked_at.is_some());
+}
+
+#[tokio::test]
+async fn test_expired_keys_are_refused() {
+    let store = Store::open_in_memory().unwrap();
+    let (_, token) = store
+        .create_api_key(NewApiKey {
+            expires_at: Some(Utc::now() - Duration::minutes(1)),
+            scopes: vec![Scope::Admin],
+            ..new_key()
+        })
+        .await
+        .unwrap();
+
+    assert!(store.authenticate(&token).await.unwrap().is_none());
+}
@@ -0,0 +1,91 @@
+use chrono::{Duration, Utc};
+use store::{IdempotencyClaim, Store};
+
+#[tokio::test]
+async fn test_idempotency_key_replays_first_response() {
+    let store = Store::open_in_memory().unwrap();
+    let window = Duration::hours(24);
+    let now = Utc::now();
+    let claim = |key: &'static str, hash: &'static str, now| {
+        let store = store.clone();
+        async move {
+            store
+                .claim_idempotency_key("api-key", key, hash, window, now)
+                .await
+                .unwrap()
+        }
+    };
+
+    assert_eq!(claim("abc", "hash-1", now).await, IdempotencyClaim::New);
+    assert_eq!(
+        claim("abc", "hash-1", now).await,
+        IdempotencyClaim::InProgress
+    );
+
+    store
+        .complete_idempotency_key(
+            "api-key",
+            "abc",
+            200,
+            vec![("x-quota-daily-remaining".to_string(), "9".to_string())],
+            b"{\"id\":\"1\"}".to_vec(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        claim("abc", "hash-1", now).await,
+        IdempotencyClaim::Replay {
+            status: 200,
+            headers: vec![("x-quota-daily-remaining".to_string(), "9".to_string())],
+            body: b"{\"id\":\"1\"}".to_vec()
+        }
+    );
+    assert_eq!(
+        claim("abc", "hash-2", now).await,
+        IdempotencyClaim::Mismatch
+    );
+
+    // Outside the window the key can be used afresh
+    let later = now + Duration::hours(25);
+    assert_eq!(claim("abc", "hash-2", later).await, IdempotencyClaim::New);
+
+    store
+        .release_idempotency_key("api-key", "abc")
+        .await
+        .unwrap();
+    assert_eq!(claim("abc", "hash-1", later).await, IdempotencyClaim::New);
+}
+
+#[tokio::test]
+async fn test_abandoned_claim_can_be_claimed_again() {
+    let store = Store::open_in_memory().unwrap();
+    let window = Duration::hours(24);
+    let now = Utc::now();
+
+    store
+        .claim_idempotency_key("api-key", "abc", "hash", window, now)
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .claim_idempotency_key("api-key", "abc", "hash", window, now + Duration::minutes(1))
+            .await
+            .unwrap(),
+        IdempotencyClaim::InProgress
+    );
+
+    // No response ever came, so the first request is assumed lost
+    assert_eq!(
+        store
+            .claim_idempotency_key(
+                "api-key",
+                "abc",
+                "hash",
+                window,
+                now + Duration::minutes(10)
+            )
+            .await
+            .unwrap(),
+        IdempotencyClaim::New
+    );
+}
@@ -0,0 +1,73 @@
+use shared::SmsRequest;
+use store::{NewInbound, Received, Store};
+
+fn inbound(body: &str) -> NewInbound {
+    NewInbound {
+        from: "+61411111111".to_string(),
+        to: "+61400000000".to_string(),
+        body: body.to_string(),
+        clicksend_message_id: None,
+        reply_to: None,
+    }
+}
+
+#[tokio::test]
+async fn test_list_inbound_newest_first() {
+    let store = Store::open_in_memory().unwrap();
+    store.insert_inbound(inbound("first")).await.unwrap();
+    store.insert_inbound(inbound("second")).await.unwrap();
+
+    let messages = store.list_inbound(None, 10).await.unwrap();
+    let bodies: Vec<_> = messages.iter().map(|sms| sms.body.as_str()).collect();
+    assert_eq!(bodies, vec!["second", "first"]);
+
+    assert_eq!(store.list_inbound(None, 1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_redelivered_inbound_is_stored_once() {
+    let store = Store::open_in_memory().unwrap();
+    let with_id = || NewInbound {
+        clicksend_message_id: Some("CS-IN-1".to_string()),
+        ..inbound("hello")
+    };
+
+    let Received::New(first) = store.insert_inbound(with_id()).await.unwrap() else {
+        panic!("first delivery should be new");
+    };
+    let Received::Duplicate(repeat) = store.insert_inbound(with_id()).await.unwrap() else {
+        panic!("redelivery should be a duplicate");
+    };
+    assert_eq!(repeat.id, first.id);
+
+    // Messages without a ClickSend ID can't be told apart, so all are kept
+    store.insert_inbound(inbound("hello")).await.unwrap();
+    store.insert_inbound(inbound("hello")).await.unwrap();
+    assert_eq!(store.list_inbound(None, 10).await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_list_inbound_for_a_key_shows_only_its_replies() {
+    let store = Store::open_in_memory().unwrap();
+    let sms = SmsRequest {
+        phone_number: "+61411111111".to_string(),
+        message: "Reply YES".to_string(),
+        send_at: None,
+    };
+    let ours = store.insert_message(&sms, "ours").await.unwrap();
+    let theirs = store.insert_message(&sms, "theirs").await.unwrap();
+    for (body, reply_to) in [("yes", Some(ours.id)), ("no", Some(theirs.id)), ("?", None)] {
+        store
+            .insert_inbound(NewInbound {
+                reply_to,
+                ..inbound(body)
+            })
+            .await
+            .unwrap();
+    }
+
+    let messages = store.list_inbound(Some("ours"), 10).await.unwrap();
+    let bodies: Vec<_> = messages.iter().map(|sms| sms.body.as_str()).collect();
+    assert_eq!(bodies, vec!["yes"]);
+    assert_eq!(store.list_inbound(None, 10).await.unwrap().len(), 3);
+}
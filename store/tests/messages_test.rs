@@ -0,0 +1,212 @@
+use chrono::{Duration, Utc};
+use shared::{MessageStatus, SmsRequest};
+use store::{AppError, Store};
+
+fn sms() -> SmsRequest {
+    SmsRequest {
+        phone_number: "+61400000000".to_string(),
+        message: "Test message".to_string(),
+        send_at: None,
+    }
+}
+
+fn reclaim_after() -> Duration {
+    Duration::minutes(15)
+}
+
+#[tokio::test]
+async fn test_status_lifecycle_is_recorded() {
+    let store = Store::open_in_memory().unwrap();
+    let record = store.insert_message(&sms(), "key").await.unwrap();
+    assert_eq!(record.status, MessageStatus::Accepted);
+
+    store
+        .set_status(&record.id, MessageStatus::Queued, None)
+        .await
+        .unwrap();
+    store
+        .set_status(&record.id, MessageStatus::Failed, Some("Invalid sender"))
+        .await
+        .unwrap();
+
+    let stored = store.get_message(&record.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MessageStatus::Failed);
+    assert_eq!(stored.api_key_id.as_deref(), Some("key"));
+    assert_eq!(stored.last_error.as_deref(), Some("Invalid sender"));
+
+    let statuses: Vec<_> = store
+        .message_events(&record.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| event.status)
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            MessageStatus::Accepted,
+            MessageStatus::Queued,
+            MessageStatus::Failed
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_set_status_of_unknown_message() {
+    let store = Store::open_in_memory().unwrap();
+
+    let result = store.set_status("missing", MessageStatus::Sent, None).await;
+
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_delivery_receipt_matches_clicksend_id() {
+    let store = Store::open_in_memory().unwrap();
+    let record = store.insert_message(&sms(), "key").await.unwrap();
+    assert!(!store.is_sent(&record.id).await.unwrap());
+    store.mark_sent(&record.id, Some("CS-123")).await.unwrap();
+    assert!(store.is_sent(&record.id).await.unwrap());
+
+    let found = store
+        .find_by_clicksend_reference(Some("CS-123"), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, record.id);
+
+    store
+        .record_delivery(
+            &record.id,
+            MessageStatus::Undelivered,
+            Some("301"),
+            Some("Handset unreachable"),
+        )
+        .await
+        .unwrap();
+
+    let stored = store.get_message(&record.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MessageStatus::Undelivered);
+    assert_eq!(stored.clicksend_message_id.as_deref(), Some("CS-123"));
+    assert_eq!(stored.error_code.as_deref(), Some("301"));
+}
+
+#[tokio::test]
+async fn test_scheduled_messages_are_claimed_when_due() {
+    let store = Store::open_in_memory().unwrap();
+    let now = Utc::now();
+    let due = store
+        .insert_message(
+            &SmsRequest {
+                send_at: Some(now - Duration::seconds(1)),
+                ..sms()
+            },
+            "key",
+        )
+        .await
+        .unwrap();
+    let later = store
+        .insert_message(
+            &SmsRequest {
+                send_at: Some(now + Duration::hours(1)),
+                ..sms()
+            },
+            "key",
+        )
+        .await
+        .unwrap();
+    assert_eq!(due.status, MessageStatus::Scheduled);
+
+    let claimed = store
+        .claim_due_messages(now, reclaim_after(), 10)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].id, due.id);
+    assert!(store
+        .claim_due_messages(now, reclaim_after(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Only messages still waiting can be cancelled
+    assert!(!store.cancel_scheduled(&due.id).await.unwrap());
+    assert!(store.cancel_scheduled(&later.id).await.unwrap());
+    let cancelled = store.get_message(&later.id).await.unwrap().unwrap();
+    assert_eq!(cancelled.status, MessageStatus::Cancelled);
+    assert!(store
+        .claim_due_messages(now + Duration::hours(2), Duration::hours(3), 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_delivery_receipt_matches_custom_string_alone() {
+    let store = Store::open_in_memory().unwrap();
+    let record = store.insert_message(&sms(), "key").await.unwrap();
+
+    // The receipt raced ahead of `mark_sent`, so only our ID can match
+    for clicksend_id in [None, Some("CS-unknown")] {
+        let found = store
+            .find_by_clicksend_reference(clicksend_id, Some(&record.id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, record.id);
+    }
+}
+
+#[tokio::test]
+async fn test_mark_sent_keeps_an_earlier_receipt() {
+    let store = Store::open_in_memory().unwrap();
+    let record = store.insert_message(&sms(), "key").await.unwrap();
+
+    store
+        .record_delivery(&record.id, MessageStatus::Delivered, None, None)
+        .await
+        .unwrap();
+    store.mark_sent(&record.id, Some("CS-123")).await.unwrap();
+
+    let stored = store.get_message(&record.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MessageStatus::Delivered);
+    assert_eq!(stored.clicksend_message_id.as_deref(), Some("CS-123"));
+}
+
+#[tokio::test]
+async fn test_claims_left_queued_are_reclaimed() {
+    let store = Store::open_in_memory().unwrap();
+    let now = Utc::now();
+    let due = store
+        .insert_message(
+            &SmsRequest {
+                send_at: Some(now - Duration::seconds(1)),
+                ..sms()
+            },
+            "key",
+        )
+        .await
+        .unwrap();
+    let immediate = store.insert_message(&sms(), "key").await.unwrap();
+    store
+        .set_status(&immediate.id, MessageStatus::Queued, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .claim_due_messages(now, reclaim_after(), 10)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Whoever claimed it died before publishing
+    let later = now + reclaim_after() + Duration::minutes(1);
+    let reclaimed = store
+        .claim_due_messages(later, reclaim_after(), 10)
+        .await
+        .unwrap();
+    assert_eq!(reclaimed.len(), 1);
+    assert_eq!(reclaimed[0].id, due.id);
+}
@@ -0,0 +1,22 @@
+use store::Store;
+
+#[tokio::test]
+async fn test_suppress_and_unsuppress() {
+    let store = Store::open_in_memory().unwrap();
+    store.suppress("+61411111111", "STOP").await.unwrap();
+    // Opting out twice is harmless
+    store.suppress("+61411111111", "UNSUBSCRIBE").await.unwrap();
+
+    assert!(store.is_suppressed("+61411111111").await.unwrap());
+    assert!(!store.is_suppressed("+61422222222").await.unwrap());
+
+    let suppressed = store
+        .suppressed_among(vec!["+61411111111".into(), "+61422222222".into()])
+        .await
+        .unwrap();
+    assert_eq!(suppressed.len(), 1);
+    assert!(suppressed.contains("+61411111111"));
+
+    store.unsuppress("+61411111111").await.unwrap();
+    assert!(!store.is_suppressed("+61411111111").await.unwrap());
+}
@@ -0,0 +1,38 @@
+use chrono::{Duration, Utc};
+use store::{QuotaLimits, QuotaUsage, Reservation, Store};
+
+#[tokio::test]
+async fn test_reserve_quota_stops_at_limit() {
+    let store = Store::open_in_memory().unwrap();
+    let limits = QuotaLimits {
+        daily: Some(10),
+        monthly: None,
+    };
+    let now = Utc::now();
+
+    let usage = store.reserve_quota("key", 8, limits, now).await.unwrap();
+    let used = QuotaUsage {
+        daily: 8,
+        monthly: 8,
+    };
+    assert_eq!(usage, Reservation::Reserved(used));
+    // A batch that doesn't fit counts nothing
+    assert_eq!(
+        store.reserve_quota("key", 3, limits, now).await.unwrap(),
+        Reservation::Exceeded(used)
+    );
+    assert!(matches!(
+        store.reserve_quota("key", 2, limits, now).await.unwrap(),
+        Reservation::Reserved(_)
+    ));
+    assert_eq!(store.quota_usage("key", now).await.unwrap().daily, 10);
+
+    // Other keys have their own quota
+    assert!(matches!(
+        store.reserve_quota("other", 1, limits, now).await.unwrap(),
+        Reservation::Reserved(_)
+    ));
+    // ...and the daily count starts over the next day
+    let tomorrow = now + Duration::days(1);
+    assert_eq!(store.quota_usage("key", tomorrow).await.unwrap().daily, 0);
+}
@@ -0,0 +1,269 @@
+use std::{env, str::FromStr, time::Duration};
+
+use clicksend::{
+    circuit::CircuitBreakerConfig,
+    clicksend::client::{RetryPolicy as HttpRetryPolicy, Timeouts},
+    provider::{twilio, ClickSendProvider, RouteOptions, SmsRouter, TwilioClient},
+    AppResult, ClickSendClient, SmsProvider,
+};
+use queue::topology::RetryPolicy;
+
+/// Runtime settings for the SMS worker, read from the environment (and `.env`).
+#[derive(Debug)]
+pub struct WorkerConfig {
+    pub amqp_url: String,
+    /// Queue to consume; must match the API's `amqp.outbound_queue`.
+    pub queue: String,
+    pub database_path: String,
+    pub prefetch: u16,
+    pub retry_policy: RetryPolicy,
+    /// How often to look for scheduled messages that have come due.
+    pub scheduler_interval: Duration,
+    /// How long a claimed scheduled message may stay `queued` before it is
+    /// assumed lost and claimed again.
+    pub scheduler_reclaim_after: Duration,
+    pub sender: String,
+    /// Who messages are sent through: the comma-separated `SMS_PROVIDER`
+    /// route names. Each route's settings are read from variables named
+    /// after it, e.g. `<ROUTE>_KIND`, `<ROUTE>_USERNAME` and
+    /// `<ROUTE>_WEIGHT`, so one provider can serve several routes.
+    pub providers: Vec<RouteConfig>,
+    pub circuit_breaker: CircuitBreakerConfig,
+}
+
+#[derive(Debug)]
+pub struct RouteConfig {
+    pub provider: ProviderConfig,
+    pub options: RouteOptions,
+}
+
+#[derive(Debug)]
+pub enum ProviderConfig {
+    ClickSend {
+        username: String,
+        api_key: String,
+        base_url: String,
+        version: String,
+        retry_policy: HttpRetryPolicy,
+        timeouts: Timeouts,
+        /// How long sender lists are trusted before being fetched again.
+        sender_cache_ttl: Duration,
+        /// How often sender lists are refreshed in the background, if at all.
+        sender_refresh: Option<Duration>,
+    },
+    Twilio {
+        account_sid: String,
+        auth_token: String,
+        base_url: String,
+        timeouts: Timeouts,
+        /// Where Twilio should post delivery status updates.
+        status_callback: Option<String>,
+    },
+}
+
+impl RouteConfig {
+    fn from_env(name: &str) -> Result<Self, String> {
+        let key = route_key(name);
+        let prefixes = env::var(key("PREFIXES"))
+            .map(|prefixes| {
+                prefixes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|prefix| !prefix.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(RouteConfig {
+            provider: ProviderConfig::from_env(name)?,
+            options: RouteOptions {
+                name: name.to_string(),
+                weight: parse_or(&key("WEIGHT"), 1)?,
+                prefixes,
+                sender: env::var(key("SENDER")).ok(),
+            },
+        })
+    }
+}
+
+impl ProviderConfig {
+    /// Reads the settings of route `name`. Its provider is `<ROUTE>_KIND`,
+    /// which routes named after a provider may leave out.
+    fn from_env(name: &str) -> Result<Self, String> {
+        let key = route_key(name);
+        let kind = match env::var(key("KIND")) {
+            Ok(kind) => kind.to_lowercase(),
+            Err(_) if matches!(name, "clicksend" | "twilio") => name.to_string(),
+            Err(_) => return Err(format!("{} must be set", key("KIND"))),
+        };
+
+        let defaults = Timeouts::default();
+        let timeouts = Timeouts {
+            connect: positive_secs(&key("CONNECT_TIMEOUT_SECS"), defaults.connect.as_secs())?,
+            request: positive_secs(&key("TIMEOUT_SECS"), defaults.request.as_secs())?,
+        };
+
+        match kind.as_str() {
+            "clicksend" => Ok(ProviderConfig::ClickSend {
+                username: required(&key("USERNAME"))?,
+                api_key: required(&key("API_KEY"))?,
+                base_url: env_or(&key("BASE_URL"), "https://rest.clicksend.com"),
+                version: env_or(&key("VERSION"), "v3"),
+                retry_policy: HttpRetryPolicy {
+                    max_attempts: parse_or(&key("MAX_ATTEMPTS"), 3)?,
+                    base_delay: Duration::from_millis(parse_or(&key("RETRY_BASE_DELAY_MS"), 200)?),
+                    max_delay: Duration::from_millis(parse_or(&key("RETRY_MAX_DELAY_MS"), 5_000)?),
+                },
+                timeouts,
+                sender_cache_ttl: Duration::from_secs(parse_or(&key("SENDER_CACHE_SECS"), 300)?),
+                sender_refresh: match parse_or(&key("SENDER_REFRESH_SECS"), 60)? {
+                    0 => None,
+                    secs => Some(Duration::from_secs(secs)),
+                },
+            }),
+            "twilio" => Ok(ProviderConfig::Twilio {
+                account_sid: required(&key("ACCOUNT_SID"))?,
+                auth_token: required(&key("AUTH_TOKEN"))?,
+                base_url: env_or(&key("BASE_URL"), twilio::DEFAULT_BASE_URL),
+                timeouts,
+                status_callback: env::var(key("STATUS_CALLBACK")).ok(),
+            }),
+            other => Err(format!(
+                "{} must be clicksend or twilio, got '{}'",
+                key("KIND"),
+                other
+            )),
+        }
+    }
+
+    /// Builds the configured provider's client, starting any background
+    /// tasks it needs. Circuit breaking is left to the router.
+    pub fn connect(&self) -> AppResult<Box<dyn SmsProvider>> {
+        Ok(match self {
+            ProviderConfig::ClickSend {
+                username,
+                api_key,
+                base_url,
+                version,
+                retry_policy,
+                timeouts,
+                sender_cache_ttl,
+                sender_refresh,
+            } => {
+                let client = ClickSendClient::new(api_key, username, base_url, version)?
+                    .with_retry_policy(retry_policy.clone())
+                    .with_timeouts(timeouts.clone())?
+                    .without_circuit_breaker()
+                    .with_sender_cache_ttl(*sender_cache_ttl);
+                if let Some(interval) = sender_refresh {
+                    client.spawn_sender_refresh(*interval);
+                }
+                Box::new(ClickSendProvider::new(client))
+            }
+            ProviderConfig::Twilio {
+                account_sid,
+                auth_token,
+                base_url,
+                timeouts,
+                status_callback,
+            } => {
+                let client = TwilioClient::new(account_sid, auth_token, base_url)?
+                    .with_timeouts(timeouts.clone())?;
+                Box::new(match status_callback {
+                    Some(url) => client.with_status_callback(url),
+                    None => client,
+                })
+            }
+        })
+    }
+}
+
+impl WorkerConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = RetryPolicy::default();
+        let retry_policy = RetryPolicy {
+            max_attempts: parse_or("MAX_DELIVERY_ATTEMPTS", defaults.max_attempts)?,
+            base_delay: Duration::from_secs(parse_or(
+                "RETRY_BASE_DELAY_SECS",
+                defaults.base_delay.as_secs(),
+            )?),
+        };
+
+        Ok(WorkerConfig {
+            amqp_url: env_or("AMQP_URL", "amqp://127.0.0.1:5672/%2f"),
+            queue: env_or("SMS_QUEUE", "sms_queue"),
+            database_path: env_or("DATABASE_PATH", "messaging.db"),
+            prefetch: parse_or("WORKER_PREFETCH", 10)?,
+            retry_policy,
+            scheduler_interval: positive_secs("SCHEDULER_INTERVAL_SECS", 5)?,
+            scheduler_reclaim_after: positive_secs("SCHEDULER_RECLAIM_SECS", 900)?,
+            sender: required("SMS_SENDER")?,
+            providers: providers_from_env()?,
+            circuit_breaker: CircuitBreakerConfig {
+                failure_threshold: parse_or("CIRCUIT_FAILURE_THRESHOLD", 5)?,
+                open_for: Duration::from_secs(parse_or("CIRCUIT_OPEN_SECS", 30)?),
+            },
+        })
+    }
+
+    /// Builds every configured provider's client behind one router, which
+    /// keeps a circuit breaker per route.
+    pub fn sms_router(&self) -> AppResult<SmsRouter> {
+        self.providers.iter().try_fold(
+            SmsRouter::new(self.circuit_breaker.clone()),
+            |router, route| Ok(router.with_route(route.provider.connect()?, route.options.clone())),
+        )
+    }
+}
+
+fn providers_from_env() -> Result<Vec<RouteConfig>, String> {
+    let names: Vec<String> = env_or("SMS_PROVIDER", "clicksend")
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    let repeated = names
+        .iter()
+        .enumerate()
+        .any(|(index, name)| names[..index].contains(name));
+    if names.is_empty() || repeated {
+        return Err("SMS_PROVIDER must list each route name once".to_string());
+    }
+
+    names
+        .iter()
+        .map(|name| RouteConfig::from_env(name))
+        .collect()
+}
+
+/// Names of route `name`'s settings: route `clicksend-au` reads
+/// `CLICKSEND_AU_USERNAME` and so on.
+fn route_key(name: &str) -> impl Fn(&str) -> String {
+    let prefix = name.to_uppercase().replace('-', "_");
+    move |setting| format!("{}_{}", prefix, setting)
+}
+
+fn required(key: &str) -> Result<String, String> {
+    env::var(key).map_err(|_| format!("{} must be set", key))
+}
+
+fn env_or(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or_else(|_| default.to_string())
+}
+
+fn positive_secs(key: &str, default: u64) -> Result<Duration, String> {
+    match parse_or(key, default)? {
+        0 => Err(format!("{} must be greater than 0", key)),
+        secs => Ok(Duration::from_secs(secs)),
+    }
+}
+
+fn parse_or<T: FromStr>(key: &str, default: T) -> Result<T, String> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| format!("{} must be a number, got '{}'", key, value)),
+        Err(_) => Ok(default),
+    }
+}
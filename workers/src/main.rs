@@ -1,3 +1,113 @@
-fn main() {
-    println!("Hello, world!");
+use std::time::Duration;
+
+use clicksend::SmsProvider;
+use queue::{
+    consumer::{Consumer, RetryOutcome},
+    topology::Topology,
+    AppError, AppResult,
+};
+use shared::{MessageStatus, QueuedSms};
+use store::Store;
+use tracing::{error, info, warn};
+use workers::{
+    config::WorkerConfig,
+    processor::{self, record_status, Outcome},
+    scheduler,
+};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let config = match WorkerConfig::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Invalid worker configuration: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let router = match config.sms_router() {
+        Ok(router) => router,
+        Err(err) => {
+            eprintln!("Failed to initialize SMS providers: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let names: Vec<_> = router.health().into_iter().map(|(name, _)| name).collect();
+    info!("Sending through {}", names.join(", "));
+
+    let store = match Store::open(&config.database_path) {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("Failed to open message store: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    tokio::spawn(scheduler::run(
+        store.clone(),
+        config.amqp_url.clone(),
+        config.queue.clone(),
+        config.scheduler_interval,
+        config.scheduler_reclaim_after,
+    ));
+
+    // The consumer has no connection recovery of its own: when the broker
+    // goes away, start over with a fresh connection.
+    loop {
+        match run(&config, &router, &store).await {
+            Ok(()) => warn!("Consumer stream for {} ended, reconnecting", config.queue),
+            Err(err) => error!("Consumer failed, reconnecting: {}", err),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn run(config: &WorkerConfig, provider: &dyn SmsProvider, store: &Store) -> AppResult<()> {
+    let topology = Topology::new(&config.queue).with_retry_policy(config.retry_policy.clone());
+    let dead_letter_queue = topology.dead_letter_queue();
+    let mut consumer: Consumer<QueuedSms> =
+        Consumer::new(&config.amqp_url, topology, config.prefetch).await?;
+
+    info!("Waiting for messages on {}", config.queue);
+
+    while let Some(message) = consumer.next().await {
+        let message = match message {
+            Ok(message) => message,
+            Err(AppError::SerializationError(err)) => {
+                warn!("Discarded malformed message: {}", err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        let id = message.payload.id.clone();
+        if message.redelivered() {
+            warn!("Message {} was redelivered", id);
+        }
+
+        match processor::process(provider, store, &config.sender, &message.payload).await {
+            Outcome::Ack => message.ack().await?,
+            Outcome::DeadLetter(err) => message.dead_letter(&err).await?,
+            Outcome::Retry(err) => match message.retry(&err).await? {
+                RetryOutcome::Scheduled { retry } => {
+                    info!("Scheduled retry {} of message {}", retry, id);
+                    record_status(store, &id, MessageStatus::Queued, Some(&err)).await;
+                }
+                RetryOutcome::DeadLettered => {
+                    warn!(
+                        "Retries exhausted for message {}, moved to {}",
+                        id, dead_letter_queue
+                    );
+                    record_status(store, &id, MessageStatus::Failed, Some(&err)).await;
+                }
+            },
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,100 @@
+use clicksend::{clicksend::client::OutboundSms, SmsProvider};
+use shared::{MessageStatus, QueuedSms, SUPPRESSED_MESSAGE};
+use store::Store;
+use tracing::{error, info, warn};
+
+/// What to do with a delivery once it has been handled.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Done with the message: it was handed to the provider (now or on an
+    /// earlier delivery), or deliberately skipped.
+    Ack,
+    /// The message can never succeed (invalid number or sender, rejected by the provider).
+    DeadLetter(String),
+    /// The provider could not be reached or had a transient failure; try again later.
+    Retry(String),
+}
+
+/// Sends `sms`, unless it was already sent by an earlier delivery that never
+/// got acked.
+pub async fn process(
+    provider: &dyn SmsProvider,
+    store: &Store,
+    sender: &str,
+    sms: &QueuedSms,
+) -> Outcome {
+    let request = &sms.request;
+
+    match store.is_sent(&sms.id).await {
+        Ok(false) => {}
+        Ok(true) => {
+            info!("Skipping message {}: already sent", sms.id);
+            return Outcome::Ack;
+        }
+        Err(err) => return Outcome::Retry(format!("Failed to check for duplicates: {}", err)),
+    }
+
+    // The recipient may have opted out after the message was queued
+    match store.is_suppressed(&request.phone_number).await {
+        Ok(false) => {}
+        Ok(true) => {
+            info!("Skipping message {}: recipient opted out", sms.id);
+            record_status(
+                store,
+                &sms.id,
+                MessageStatus::Failed,
+                Some(SUPPRESSED_MESSAGE),
+            )
+            .await;
+            return Outcome::Ack;
+        }
+        Err(err) => return Outcome::Retry(format!("Failed to check suppression list: {}", err)),
+    }
+
+    record_status(store, &sms.id, MessageStatus::Sending, None).await;
+
+    let outbound = OutboundSms {
+        to: request.phone_number.clone(),
+        from: sender.to_string(),
+        body: request.message.clone(),
+        // Lets a receipt arriving before `mark_sent` still find the message
+        custom_string: Some(sms.id.clone()),
+    };
+    match provider.send(&outbound).await {
+        Ok(result) => {
+            info!(
+                "Sent message {} to {} as provider message {}",
+                sms.id,
+                request.phone_number,
+                result.message_id.as_deref().unwrap_or("(none)")
+            );
+            if let Err(err) = store.mark_sent(&sms.id, result.message_id.as_deref()).await {
+                error!("Failed to mark message {} as sent: {}", sms.id, err);
+            }
+            Outcome::Ack
+        }
+        Err(err) if err.is_transient() => {
+            warn!("Failed to send message {}: {}", sms.id, err);
+            Outcome::Retry(err.to_string())
+        }
+        Err(err) => {
+            warn!("Rejecting message {}: {}", sms.id, err);
+            record_status(
+                store,
+                &sms.id,
+                MessageStatus::Failed,
+                Some(&err.to_string()),
+            )
+            .await;
+            Outcome::DeadLetter(err.to_string())
+        }
+    }
+}
+
+/// Updates the stored status. Failures are logged rather than returned: the
+/// delivery itself shouldn't fail because its bookkeeping did.
+pub async fn record_status(store: &Store, id: &str, status: MessageStatus, error: Option<&str>) {
+    if let Err(err) = store.set_status(id, status, error).await {
+        error!("Failed to mark message {} as {}: {}", id, status, err);
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use queue::publisher::{QueueNames, RabbitMQ};
+use shared::MessageStatus;
+use store::{StatusUpdate, Store};
+use tracing::{error, info, warn};
+
+/// Most scheduled messages published per tick.
+const BATCH_SIZE: u32 = 500;
+
+/// Publishes scheduled messages to `queue` as they come due, checking the
+/// store every `interval`, and republishes ones claimed over `reclaim_after`
+/// ago that never left `queued`. Runs forever.
+pub async fn run(
+    store: Store,
+    amqp_url: String,
+    queue: String,
+    interval: Duration,
+    reclaim_after: Duration,
+) {
+    let reclaim_after = chrono::Duration::from_std(reclaim_after).unwrap_or(chrono::Duration::MAX);
+    let queues = QueueNames {
+        outbound: queue,
+        ..QueueNames::default()
+    };
+    let rabbitmq = loop {
+        match RabbitMQ::with_queues(&amqp_url, queues.clone()).await {
+            Ok(rabbitmq) => break rabbitmq,
+            Err(err) => {
+                error!("Scheduler failed to connect to RabbitMQ: {}", err);
+                tokio::time::sleep(interval).await;
+            }
+        }
+    };
+
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = publish_due(&store, &rabbitmq, reclaim_after).await {
+            error!("Failed to publish scheduled messages: {}", err);
+        }
+    }
+}
+
+async fn publish_due(
+    store: &Store,
+    rabbitmq: &RabbitMQ,
+    reclaim_after: chrono::Duration,
+) -> store::AppResult<()> {
+    let due = store
+        .claim_due_messages(Utc::now(), reclaim_after, BATCH_SIZE)
+        .await?;
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    let queued: Vec<_> = due.iter().map(|record| record.to_queued()).collect();
+    let published = rabbitmq.publish_messages(&queued).await;
+
+    // Messages that couldn't be published go back to waiting, to be picked
+    // up again on the next tick.
+    let mut updates = Vec::new();
+    for (sms, outcome) in queued.into_iter().zip(published) {
+        if let Err(err) = outcome {
+            warn!("Failed to publish scheduled message {}: {}", sms.id, err);
+            updates.push(StatusUpdate {
+                id: sms.id,
+                status: MessageStatus::Scheduled,
+                error: Some(err.to_string()),
+            });
+        }
+    }
+
+    info!(
+        "Published {} of {} scheduled messages",
+        due.len() - updates.len(),
+        due.len()
+    );
+    store.set_statuses(updates).await
+}
@@ -0,0 +1,54 @@
+use std::env;
+
+use serial_test::serial;
+use workers::config::{ProviderConfig, WorkerConfig};
+
+#[test]
+#[serial]
+fn test_routes_are_configured_by_name() {
+    let vars = [
+        ("SMS_SENDER", "+61411111111"),
+        ("SMS_PROVIDER", "clicksend-au, clicksend-nz"),
+        ("CLICKSEND_AU_KIND", "clicksend"),
+        ("CLICKSEND_AU_USERNAME", "au-user"),
+        ("CLICKSEND_AU_API_KEY", "au-key"),
+        ("CLICKSEND_AU_PREFIXES", "+61"),
+        ("CLICKSEND_NZ_KIND", "clicksend"),
+        ("CLICKSEND_NZ_USERNAME", "nz-user"),
+        ("CLICKSEND_NZ_API_KEY", "nz-key"),
+        ("CLICKSEND_NZ_PREFIXES", "+64"),
+    ];
+    for (key, value) in vars {
+        env::set_var(key, value);
+    }
+
+    let config = WorkerConfig::from_env().unwrap();
+    let routes: Vec<_> = config
+        .providers
+        .iter()
+        .map(|route| match &route.provider {
+            ProviderConfig::ClickSend { username, .. } => {
+                (route.options.name.as_str(), username.as_str())
+            }
+            other => panic!("Expected ClickSend, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        routes,
+        [("clicksend-au", "au-user"), ("clicksend-nz", "nz-user")]
+    );
+    assert_eq!(config.providers[1].options.prefixes, ["+64"]);
+
+    env::set_var("SMS_PROVIDER", "clicksend-au,clicksend-au");
+    assert!(WorkerConfig::from_env().is_err());
+
+    env::set_var("SMS_PROVIDER", "backup");
+    assert_eq!(
+        WorkerConfig::from_env().unwrap_err(),
+        "BACKUP_KIND must be set"
+    );
+
+    for (key, _) in vars {
+        env::remove_var(key);
+    }
+}
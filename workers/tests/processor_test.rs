@@ -0,0 +1,84 @@
+use std::sync::Mutex;
+
+use clicksend::{
+    clicksend::client::OutboundSms,
+    provider::{Capabilities, SentSms},
+    AppResult, SmsProvider,
+};
+use shared::{MessageStatus, SmsRequest};
+use store::Store;
+use workers::processor::{process, Outcome};
+
+/// Accepts everything, remembering what it was asked to send.
+#[derive(Default)]
+struct FakeProvider {
+    sent: Mutex<Vec<OutboundSms>>,
+}
+
+#[async_trait::async_trait]
+impl SmsProvider for FakeProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            bulk_send: false,
+            max_batch_size: 1,
+            alpha_senders: true,
+            delivery_receipts: true,
+            custom_references: true,
+        }
+    }
+
+    async fn validate_sender(&self, _sender: &str) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn send(&self, sms: &OutboundSms) -> AppResult<SentSms> {
+        let mut sent = self.sent.lock().unwrap();
+        sent.push(sms.clone());
+        Ok(SentSms {
+            to: sms.to.clone(),
+            message_id: Some(format!("FAKE-{}", sent.len())),
+            parts: Some(1),
+            price: None,
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_redelivery_of_a_sent_message_is_skipped() {
+    let store = Store::open_in_memory().unwrap();
+    let provider = FakeProvider::default();
+    let record = store
+        .insert_message(
+            &SmsRequest {
+                phone_number: "+61400000000".to_string(),
+                message: "Test message".to_string(),
+                send_at: None,
+            },
+            "key",
+        )
+        .await
+        .unwrap();
+    let queued = record.to_queued();
+
+    assert_eq!(
+        process(&provider, &store, "+61411111111", &queued).await,
+        Outcome::Ack
+    );
+    // The broker lost the ack and delivered the message again
+    assert_eq!(
+        process(&provider, &store, "+61411111111", &queued).await,
+        Outcome::Ack
+    );
+
+    let sent = provider.sent.lock().unwrap().clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].custom_string.as_deref(), Some(record.id.as_str()));
+
+    let stored = store.get_message(&record.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MessageStatus::Sent);
+    assert_eq!(stored.clicksend_message_id.as_deref(), Some("FAKE-1"));
+}